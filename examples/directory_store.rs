@@ -0,0 +1,87 @@
+//! A backing store keeping one file per key in a directory, used under a read-through,
+//! write-through cache.
+//!
+//! Run with `cargo run --example directory_store [directory]`. Without a directory, a
+//! scratch directory under the system temporary directory is used and removed afterwards.
+
+use generational_lru::lrucache::CacheError;
+use generational_lru::store::{CacheStore, StoreError, ThroughCache};
+use std::{
+    env, fs,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+/// Store keeping the value of every key in a file named after the key.
+struct DirectoryStore {
+    root: PathBuf,
+}
+
+impl DirectoryStore {
+    fn open(root: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&root)?;
+        Ok(DirectoryStore { root })
+    }
+
+    fn path(&self, key: &u64) -> PathBuf {
+        self.root.join(key.to_string())
+    }
+}
+
+impl CacheStore<u64, String> for DirectoryStore {
+    type Error = io::Error;
+
+    fn load(&mut self, key: &u64) -> Result<Option<String>, Self::Error> {
+        match fs::read_to_string(self.path(key)) {
+            Ok(value) => Ok(Some(value)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn store(&mut self, key: &u64, value: &String) -> Result<(), Self::Error> {
+        fs::write(self.path(key), value)
+    }
+
+    fn delete(&mut self, key: &u64) -> Result<(), Self::Error> {
+        match fs::remove_file(self.path(key)) {
+            Err(error) if error.kind() != ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn main() -> Result<(), StoreError<io::Error>> {
+    let (root, scratch) = match env::args().nth(1) {
+        Some(root) => (PathBuf::from(root), false),
+        None => (
+            env::temp_dir().join(format!("directory-store-{}", std::process::id())),
+            true,
+        ),
+    };
+
+    let store = DirectoryStore::open(root.clone()).map_err(StoreError::Store)?;
+    let mut cache = ThroughCache::with_capacity(2, store);
+
+    for key in 0..4 {
+        cache.insert(key, format!("value {}", key))?;
+    }
+
+    // 0 and 1 were evicted from the cache, but are read back from their files
+    assert!(!cache.cache().contains(&0));
+    assert_eq!(cache.get(&0)?, "value 0");
+    println!("{}: {}", root.join("0").display(), cache.get(&0)?);
+
+    cache.remove(&1)?;
+    assert!(matches!(
+        cache.get(&1),
+        Err(StoreError::Cache(CacheError::CacheMiss))
+    ));
+    assert!(!root.join("1").exists());
+
+    if scratch {
+        fs::remove_dir_all(&root).map_err(StoreError::Store)?;
+    }
+
+    Ok(())
+}
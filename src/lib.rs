@@ -42,8 +42,10 @@
 //!
 //! ```
 
-#[forbid(unsafe_code)]
+// the arena and list tests count loop iterations by hand
+#![cfg_attr(test, allow(clippy::explicit_counter_loop, clippy::unnecessary_cast))]
 
+#[forbid(unsafe_code)]
 pub mod arena {
     //! Module providing a generational arena based off a vector.
     //!
@@ -228,6 +230,23 @@ pub mod arena {
 
             None
         }
+
+        /// Returns the Index of the entry Occupied at the given position in the
+        /// underlying vector, if any. Useful for sweeping over arena slots in order.
+        pub fn index_at(&self, idx: usize) -> Option<Index> {
+            if let Some(Entry::Occupied {
+                value: _,
+                generation,
+            }) = self.items.get(idx)
+            {
+                return Some(Index {
+                    idx,
+                    generation: *generation,
+                });
+            }
+
+            None
+        }
     }
 
     #[cfg(test)]
@@ -379,6 +398,21 @@ pub mod arena {
                 }
             }
         }
+
+        #[test]
+        fn arena_index_at() {
+            let mut arena = Arena::<i32>::with_capacity(2);
+            assert!(arena.index_at(0).is_none());
+
+            let index_0 = arena.insert(0).unwrap();
+            let index_1 = arena.insert(1).unwrap();
+            assert_eq!(arena.index_at(0), Some(index_0));
+            assert_eq!(arena.index_at(1), Some(index_1));
+            assert!(arena.index_at(2).is_none());
+
+            arena.remove(&index_0).unwrap();
+            assert!(arena.index_at(0).is_none());
+        }
     }
 }
 
@@ -441,7 +475,14 @@ pub mod list {
     /// A generational arena based doubly linked list implementation.
     pub struct LinkedList<T> {
         arena: Arena<Node<T>>,
+        links: SharedList,
+    }
 
+    /// Head, tail and length of a doubly linked list whose nodes live in an arena shared
+    /// with other lists. Every operation takes the arena, which must be the one holding
+    /// the nodes of this list.
+    #[derive(Debug, Default)]
+    pub struct SharedList {
         head: Option<Link>,
         tail: Option<Link>,
 
@@ -450,7 +491,16 @@ pub mod list {
 
     /// Iterator for our LinkedList.
     pub struct Iter<'a, T: 'a> {
-        list: &'a LinkedList<T>,
+        arena: &'a Arena<Node<T>>,
+        current: Option<Link>,
+    }
+
+    /// A cursor over our LinkedList, which can remove the node it points to while
+    /// retaining its position. Like the cursors in the standard library, the cursor
+    /// moves over a "ghost" position between the tail and the head, represented by
+    /// `None`. The position may be read back as a Link to resume from later.
+    pub struct CursorMut<'a, T: 'a> {
+        list: &'a mut LinkedList<T>,
         current: Option<Link>,
     }
 
@@ -474,25 +524,20 @@ pub mod list {
         }
     }
 
-    impl<T> Default for LinkedList<T> {
-        fn default() -> Self {
-            Self::new()
-        }
+    fn node<'a, T>(arena: &'a Arena<Node<T>>, link: &Link) -> Result<&'a Node<T>, ListError> {
+        arena.get(&link.index).ok_or(ListError::LinkBroken)
     }
 
-    impl<T> LinkedList<T> {
-        pub fn new() -> Self {
-            LinkedList {
-                arena: Arena::new(),
-                head: None,
-                tail: None,
-                len: 0,
-            }
-        }
+    fn node_mut<'a, T>(
+        arena: &'a mut Arena<Node<T>>,
+        link: &Link,
+    ) -> Result<&'a mut Node<T>, ListError> {
+        arena.get_mut(&link.index).ok_or(ListError::LinkBroken)
+    }
 
-        pub fn with_capacity(capacity: usize) -> Self {
-            LinkedList {
-                arena: Arena::with_capacity(capacity),
+    impl SharedList {
+        pub fn new() -> Self {
+            SharedList {
                 head: None,
                 tail: None,
                 len: 0,
@@ -507,43 +552,29 @@ pub mod list {
             self.head.is_none()
         }
 
-        pub fn is_full(&self) -> bool {
-            self.len == self.arena.capacity()
-        }
-
-        pub fn reserve(&mut self, capacity: usize) {
-            self.arena.reserve(capacity)
-        }
-
-        pub fn get_mut(&mut self, link: &Link) -> Result<&mut Node<T>, ListError> {
-            let node = self
-                .arena
-                .get_mut(&link.index)
-                .ok_or(ListError::LinkBroken)?;
-            Ok(node)
-        }
-
-        pub fn get_mut_value(&mut self, link: &Link) -> Result<&mut T, ListError> {
-            let node = self.get_mut(link)?;
-            Ok(&mut node.value)
+        pub fn head(&self) -> Option<Link> {
+            self.head
         }
 
-        pub fn get(&self, link: &Link) -> Result<&Node<T>, ListError> {
-            let node = self.arena.get(&link.index).ok_or(ListError::LinkBroken)?;
-            Ok(node)
+        pub fn tail(&self) -> Option<Link> {
+            self.tail
         }
 
-        pub fn push_front(&mut self, value: T) -> Result<Link, ListError> {
+        pub fn push_front<T>(
+            &mut self,
+            arena: &mut Arena<Node<T>>,
+            value: T,
+        ) -> Result<Link, ListError> {
             let node = Node {
                 value,
                 next: self.head,
                 prev: None,
             };
 
-            let index = self.arena.insert(node).map_err(ListError::ListOOM)?;
+            let index = arena.insert(node).map_err(ListError::ListOOM)?;
             let link = Link { index };
             if let Some(head) = self.head {
-                let head_node = self.get_mut(&head)?;
+                let head_node = node_mut(arena, &head)?;
                 head_node.prev = Some(link);
             } else {
                 self.tail = Some(link);
@@ -555,17 +586,21 @@ pub mod list {
             Ok(link)
         }
 
-        pub fn push_back(&mut self, value: T) -> Result<Link, ListError> {
+        pub fn push_back<T>(
+            &mut self,
+            arena: &mut Arena<Node<T>>,
+            value: T,
+        ) -> Result<Link, ListError> {
             let node = Node {
                 value,
                 next: None,
                 prev: self.tail,
             };
 
-            let index = self.arena.insert(node).map_err(ListError::ListOOM)?;
+            let index = arena.insert(node).map_err(ListError::ListOOM)?;
             let link = Link { index };
             if let Some(tail) = self.tail {
-                let tail_node = self.get_mut(&tail)?;
+                let tail_node = node_mut(arena, &tail)?;
                 tail_node.next = Some(link);
             } else {
                 self.head = Some(link)
@@ -577,35 +612,56 @@ pub mod list {
             Ok(link)
         }
 
-        pub fn head(&self) -> Option<Link> {
-            self.head
-        }
+        /// Inserts a new node with the given value right before the node pointed to by
+        /// the given Link.
+        pub fn insert_before<T>(
+            &mut self,
+            arena: &mut Arena<Node<T>>,
+            link: &Link,
+            value: T,
+        ) -> Result<Link, ListError> {
+            let prev_link = node(arena, link)?.prev;
 
-        pub fn tail(&self) -> Option<Link> {
-            self.tail
+            let node = Node {
+                value,
+                next: Some(*link),
+                prev: prev_link,
+            };
+
+            let index = arena.insert(node).map_err(ListError::ListOOM)?;
+            let new_link = Link { index };
+
+            node_mut(arena, link)?.prev = Some(new_link);
+            if let Some(prev_link) = prev_link {
+                node_mut(arena, &prev_link)?.next = Some(new_link);
+            } else {
+                self.head = Some(new_link);
+            }
+
+            self.len += 1;
+            Ok(new_link)
         }
 
-        pub fn peek_front(&self) -> Result<&T, ListError> {
+        pub fn peek_front<'a, T>(&self, arena: &'a Arena<Node<T>>) -> Result<&'a T, ListError> {
             let head_link = self.head.ok_or(ListError::ListEmpty)?;
-            return self.get(&head_link).map(|x| &x.value);
+            node(arena, &head_link).map(|x| &x.value)
         }
 
-        pub fn peek_back(&self) -> Result<&T, ListError> {
+        pub fn peek_back<'a, T>(&self, arena: &'a Arena<Node<T>>) -> Result<&'a T, ListError> {
             let tail_link = self.tail.ok_or(ListError::ListEmpty)?;
-            return self.get(&tail_link).map(|x| &x.value);
+            node(arena, &tail_link).map(|x| &x.value)
         }
 
-        pub fn pop_front(&mut self) -> Result<T, ListError> {
+        pub fn pop_front<T>(&mut self, arena: &mut Arena<Node<T>>) -> Result<T, ListError> {
             let head_link = self.head.ok_or(ListError::ListEmpty)?;
-            let node = self
-                .arena
+            let node = arena
                 .remove(&head_link.index)
                 .ok_or(ListError::LinkBroken)?;
 
             self.head = node.next;
 
             if let Some(link) = self.head {
-                let cur_head_node = self.get_mut(&link)?;
+                let cur_head_node = node_mut(arena, &link)?;
                 cur_head_node.prev = None;
             } else {
                 self.tail = None;
@@ -615,16 +671,15 @@ pub mod list {
             Ok(node.value)
         }
 
-        pub fn pop_back(&mut self) -> Result<T, ListError> {
+        pub fn pop_back<T>(&mut self, arena: &mut Arena<Node<T>>) -> Result<T, ListError> {
             let tail_link = self.tail.ok_or(ListError::ListEmpty)?;
-            let node = self
-                .arena
+            let node = arena
                 .remove(&tail_link.index)
                 .ok_or(ListError::LinkBroken)?;
 
             self.tail = node.prev;
             if let Some(link) = self.tail {
-                let cur_tail_node = self.get_mut(&link)?;
+                let cur_tail_node = node_mut(arena, &link)?;
                 cur_tail_node.next = None;
             } else {
                 self.head = None;
@@ -634,29 +689,30 @@ pub mod list {
             Ok(node.value)
         }
 
-        pub fn remove(&mut self, link: &Link) -> Result<T, ListError> {
+        pub fn remove<T>(
+            &mut self,
+            arena: &mut Arena<Node<T>>,
+            link: &Link,
+        ) -> Result<T, ListError> {
             let head = self.head.ok_or(ListError::ListEmpty)?;
             let tail = self.tail.ok_or(ListError::ListEmpty)?;
 
             if link == &head {
-                return self.pop_front();
+                return self.pop_front(arena);
             }
 
             if link == &tail {
-                return self.pop_back();
+                return self.pop_back(arena);
             }
 
-            let node = self
-                .arena
-                .remove(&link.index)
-                .ok_or(ListError::LinkBroken)?;
+            let node = arena.remove(&link.index).ok_or(ListError::LinkBroken)?;
             let prev_link = node.prev.ok_or(ListError::LinkBroken)?;
             let next_link = node.next.ok_or(ListError::LinkBroken)?;
 
-            let prev = self.get_mut(&prev_link)?;
+            let prev = node_mut(arena, &prev_link)?;
             prev.next = Some(next_link);
 
-            let next = self.get_mut(&next_link)?;
+            let next = node_mut(arena, &next_link)?;
             next.prev = Some(prev_link);
 
             self.len -= 1;
@@ -665,7 +721,11 @@ pub mod list {
 
         /// Re-arranges the nodes in the linked list to make the node pointed to by the
         /// given Link the tail node.
-        pub fn reposition_to_tail(&mut self, link: &Link) -> Result<(), ListError> {
+        pub fn reposition_to_tail<T>(
+            &mut self,
+            arena: &mut Arena<Node<T>>,
+            link: &Link,
+        ) -> Result<(), ListError> {
             let head = self.head.ok_or(ListError::ListEmpty)?;
             let tail = self.tail.ok_or(ListError::ListEmpty)?;
 
@@ -675,12 +735,12 @@ pub mod list {
 
             // list has >= 2 nodes
 
-            let head_node = self.get_mut(&head)?;
+            let head_node = node_mut(arena, &head)?;
             if link == &head {
                 self.head = head_node.next;
             }
 
-            let node = self.get_mut(link)?;
+            let node = node_mut(arena, link)?;
 
             let prev_link = node.prev;
             let next_link = node.next;
@@ -689,36 +749,197 @@ pub mod list {
             node.next = None;
 
             if let Some(link) = prev_link {
-                let prev = self.get_mut(&link)?;
+                let prev = node_mut(arena, &link)?;
                 prev.next = next_link;
             }
 
             if let Some(link) = next_link {
-                let next = self.get_mut(&link)?;
+                let next = node_mut(arena, &link)?;
                 next.prev = prev_link;
             }
 
-            let tail_node = self.get_mut(&tail)?;
+            let tail_node = node_mut(arena, &tail)?;
             tail_node.next = Some(*link);
             self.tail = Some(*link);
 
             Ok(())
         }
 
-        pub fn iter(&self) -> Iter<T> {
+        pub fn iter<'a, T>(&self, arena: &'a Arena<Node<T>>) -> Iter<'a, T> {
             Iter {
-                list: self,
+                arena,
                 current: self.head(),
             }
         }
     }
 
+    impl<T> Default for LinkedList<T> {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl<T> LinkedList<T> {
+        pub fn new() -> Self {
+            LinkedList {
+                arena: Arena::new(),
+                links: SharedList::new(),
+            }
+        }
+
+        pub fn with_capacity(capacity: usize) -> Self {
+            LinkedList {
+                arena: Arena::with_capacity(capacity),
+                links: SharedList::new(),
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.links.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.links.is_empty()
+        }
+
+        pub fn is_full(&self) -> bool {
+            self.links.len() == self.arena.capacity()
+        }
+
+        pub fn capacity(&self) -> usize {
+            self.arena.capacity()
+        }
+
+        pub fn reserve(&mut self, capacity: usize) {
+            self.arena.reserve(capacity)
+        }
+
+        pub fn get_mut(&mut self, link: &Link) -> Result<&mut Node<T>, ListError> {
+            node_mut(&mut self.arena, link)
+        }
+
+        pub fn get_mut_value(&mut self, link: &Link) -> Result<&mut T, ListError> {
+            let node = self.get_mut(link)?;
+            Ok(&mut node.value)
+        }
+
+        pub fn get(&self, link: &Link) -> Result<&Node<T>, ListError> {
+            node(&self.arena, link)
+        }
+
+        pub fn push_front(&mut self, value: T) -> Result<Link, ListError> {
+            self.links.push_front(&mut self.arena, value)
+        }
+
+        pub fn push_back(&mut self, value: T) -> Result<Link, ListError> {
+            self.links.push_back(&mut self.arena, value)
+        }
+
+        /// Inserts a new node with the given value right before the node pointed to by
+        /// the given Link.
+        pub fn insert_before(&mut self, link: &Link, value: T) -> Result<Link, ListError> {
+            self.links.insert_before(&mut self.arena, link, value)
+        }
+
+        pub fn head(&self) -> Option<Link> {
+            self.links.head()
+        }
+
+        pub fn tail(&self) -> Option<Link> {
+            self.links.tail()
+        }
+
+        pub fn peek_front(&self) -> Result<&T, ListError> {
+            self.links.peek_front(&self.arena)
+        }
+
+        pub fn peek_back(&self) -> Result<&T, ListError> {
+            self.links.peek_back(&self.arena)
+        }
+
+        pub fn pop_front(&mut self) -> Result<T, ListError> {
+            self.links.pop_front(&mut self.arena)
+        }
+
+        pub fn pop_back(&mut self) -> Result<T, ListError> {
+            self.links.pop_back(&mut self.arena)
+        }
+
+        pub fn remove(&mut self, link: &Link) -> Result<T, ListError> {
+            self.links.remove(&mut self.arena, link)
+        }
+
+        /// Re-arranges the nodes in the linked list to make the node pointed to by the
+        /// given Link the tail node.
+        pub fn reposition_to_tail(&mut self, link: &Link) -> Result<(), ListError> {
+            self.links.reposition_to_tail(&mut self.arena, link)
+        }
+
+        pub fn iter(&self) -> Iter<'_, T> {
+            self.links.iter(&self.arena)
+        }
+
+        /// Returns a cursor pointing to the node pointed to by the given Link, or to the
+        /// "ghost" position if `None` is given.
+        pub fn cursor_mut(&mut self, link: Option<Link>) -> CursorMut<'_, T> {
+            CursorMut {
+                list: self,
+                current: link,
+            }
+        }
+    }
+
+    impl<'a, T: 'a> CursorMut<'a, T> {
+        /// Returns the Link to the node this cursor points to, if any.
+        pub fn current(&self) -> Option<Link> {
+            self.current
+        }
+
+        pub fn current_mut(&mut self) -> Option<&mut T> {
+            let link = self.current?;
+            self.list.get_mut_value(&link).ok()
+        }
+
+        /// Moves this cursor towards the tail. Moving from the tail leads to the "ghost"
+        /// position, while moving from the "ghost" position leads to the head.
+        pub fn move_next(&mut self) {
+            self.current = match self.current {
+                Some(link) => self.list.get(&link).ok().and_then(|node| node.next),
+                None => self.list.head(),
+            }
+        }
+
+        /// Moves this cursor towards the head. Moving from the head leads to the "ghost"
+        /// position, while moving from the "ghost" position leads to the tail.
+        pub fn move_prev(&mut self) {
+            self.current = match self.current {
+                Some(link) => self.list.get(&link).ok().and_then(|node| node.prev),
+                None => self.list.tail(),
+            }
+        }
+
+        /// Removes the node this cursor points to, moving this cursor to the next node.
+        pub fn remove_current(&mut self) -> Result<T, ListError> {
+            let link = self.current.ok_or(ListError::LinkBroken)?;
+            self.current = self.list.get(&link)?.next;
+            self.list.remove(&link)
+        }
+
+        /// Removes the node this cursor points to, moving this cursor to the previous
+        /// node.
+        pub fn remove_current_and_move_prev(&mut self) -> Result<T, ListError> {
+            let link = self.current.ok_or(ListError::LinkBroken)?;
+            self.current = self.list.get(&link)?.prev;
+            self.list.remove(&link)
+        }
+    }
+
     impl<'a, T: 'a> Iterator for Iter<'a, T> {
         type Item = &'a T;
 
         fn next(&mut self) -> Option<Self::Item> {
             if let Some(link) = self.current {
-                if let Ok(node) = self.list.get(&link) {
+                if let Ok(node) = node(self.arena, &link) {
                     self.current = node.next;
                     return Some(&node.value);
                 }
@@ -858,6 +1079,98 @@ pub mod list {
             assert!(list.iter().eq([1, 3].iter()));
         }
 
+        #[test]
+        fn list_shared_arena() {
+            let mut arena = Arena::with_capacity(3);
+            let mut odd = SharedList::new();
+            let mut even = SharedList::new();
+
+            let link_0 = even.push_back(&mut arena, 0).unwrap();
+            let link_1 = odd.push_back(&mut arena, 1).unwrap();
+            even.push_front(&mut arena, 2).unwrap();
+            assert_eq!(
+                odd.push_back(&mut arena, 3),
+                Err(ListError::ListOOM(ArenaOOM {}))
+            );
+
+            assert!(even.iter(&arena).eq([2, 0].iter()));
+            assert!(odd.iter(&arena).eq([1].iter()));
+            assert_eq!((even.len(), odd.len()), (2, 1));
+
+            // space freed by one list is reused by the other
+            assert_eq!(even.remove(&mut arena, &link_0), Ok(0));
+            let link_3 = odd.insert_before(&mut arena, &link_1, 3).unwrap();
+            assert!(odd.iter(&arena).eq([3, 1].iter()));
+            odd.reposition_to_tail(&mut arena, &link_3).unwrap();
+            assert_eq!(odd.peek_back(&arena), Ok(&3));
+            assert_eq!(even.pop_back(&mut arena), Ok(2));
+            assert!(even.is_empty());
+            assert_eq!(odd.pop_front(&mut arena), Ok(1));
+            assert_eq!(odd.peek_front(&arena), Ok(&3));
+        }
+
+        #[test]
+        fn list_insert_before() {
+            let mut list = LinkedList::<i32>::with_capacity(4);
+
+            let link_1 = list.push_back(1).unwrap();
+            let link_3 = list.push_back(3).unwrap();
+
+            let link_0 = list.insert_before(&link_1, 0).unwrap();
+            assert_eq!(list.head(), Some(link_0));
+
+            list.insert_before(&link_3, 2).unwrap();
+            assert_eq!(list.tail(), Some(link_3));
+            assert_eq!(list.len(), 4);
+
+            assert!(list.iter().eq([0, 1, 2, 3].iter()));
+            assert_eq!(
+                list.insert_before(&link_0, -1),
+                Err(ListError::ListOOM(ArenaOOM {}))
+            );
+
+            list.remove(&link_0).unwrap();
+            assert_eq!(list.insert_before(&link_0, -1), Err(ListError::LinkBroken));
+        }
+
+        #[test]
+        fn list_cursor_mut() {
+            let capacity = 5;
+            let mut list = LinkedList::<i32>::with_capacity(capacity);
+            for ele in 0..capacity {
+                list.push_back(ele as i32).unwrap();
+            }
+
+            let mut cursor = list.cursor_mut(None);
+            assert!(cursor.current_mut().is_none());
+            assert_eq!(cursor.remove_current(), Err(ListError::LinkBroken));
+
+            cursor.move_prev();
+            assert_eq!(cursor.current_mut(), Some(&mut 4));
+            cursor.move_next();
+            assert!(cursor.current().is_none());
+            cursor.move_next();
+            assert_eq!(cursor.current_mut(), Some(&mut 0));
+
+            cursor.move_next();
+            assert_eq!(cursor.remove_current(), Ok(1));
+            assert_eq!(cursor.current_mut(), Some(&mut 2));
+            *cursor.current_mut().unwrap() = -2;
+
+            assert_eq!(cursor.remove_current_and_move_prev(), Ok(-2));
+            assert_eq!(cursor.current_mut(), Some(&mut 0));
+            assert_eq!(cursor.remove_current_and_move_prev(), Ok(0));
+            assert!(cursor.current().is_none());
+
+            let link = list.tail();
+            let mut cursor = list.cursor_mut(link);
+            assert_eq!(cursor.remove_current(), Ok(4));
+            assert!(cursor.current().is_none());
+
+            assert!(list.iter().eq([3].iter()));
+            assert_eq!(list.len(), 1);
+        }
+
         #[test]
         fn list_reposition_to_tail() {
             let capacity = 5;
@@ -955,8 +1268,15 @@ pub mod lrucache {
     //!
     //! ```
 
+    use crate::budget::{BudgetClock, BudgetMember};
     use crate::list::{Link, LinkedList, ListError};
-    use std::{collections::HashMap, fmt::Display, hash::Hash};
+    use crate::rng::XorShift64;
+    use std::{
+        collections::{hash_map::DefaultHasher, HashMap, HashSet},
+        fmt::Display,
+        hash::{Hash, Hasher},
+        sync::atomic::{AtomicU64, Ordering},
+    };
 
     /// Cache block storing some key and value.
     pub struct Block<K, V> {
@@ -964,20 +1284,101 @@ pub mod lrucache {
         pub value: V,
     }
 
+    /// Block stored in an LRUCache, along with the version of its value and the time it
+    /// was last used, as per the budget clock.
+    struct Entry<K, V> {
+        block: Block<K, V>,
+        version: u64,
+        stamp: u64,
+    }
+
+    /// Function telling how much an entry weighs against the weight budget of a
+    /// BudgetCoordinator.
+    type Weigher<K, V> = Box<dyn Fn(&K, &V) -> usize + Send + Sync>;
+
+    /// Policy deciding which entry an LRUCache evicts when full.
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub enum EvictionPolicy {
+        /// Evict the least recently used entry.
+        #[default]
+        LeastRecentlyUsed,
+        /// Evict the most recently used entry. Suits cyclic scans over more keys than
+        /// the cache can hold.
+        MostRecentlyUsed,
+        /// Evict the entry inserted first. Cache hits do not re-arrange entries.
+        FirstInFirstOut,
+    }
+
+    /// Policy deciding where an LRUCache places newly inserted entries.
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub enum InsertionPolicy {
+        /// Insert at the most recently used end.
+        #[default]
+        MostRecentlyUsed,
+        /// LRU Insertion Policy: insert at the least recently used end, so that an entry
+        /// has to be hit once before it is protected from eviction.
+        LeastRecentlyUsed,
+        /// Bimodal Insertion Policy: insert at the most recently used end with the given
+        /// probability, within `[0, 1]`, at the least recently used end otherwise.
+        Bimodal { epsilon: f64 },
+        /// Dynamic Insertion Policy: a few leader sets of keys always insert at the most
+        /// recently used end, a few others insert bimodally. The remaining keys follow
+        /// whichever leader sets missed less.
+        Dynamic { epsilon: f64 },
+    }
+
+    /// Number of sets keys are hashed into under the dynamic insertion policy. Set 0
+    /// leads for MRU insertion and set 1 leads for bimodal insertion.
+    pub const DUELING_SETS: u64 = 32;
+
+    /// Saturation limit of the counter tracking which leader sets missed more.
+    const PSEL_MAX: u32 = 1023;
+
+    /// Source of the ids telling LRUCache instances apart, so that a handle is only
+    /// honoured by the cache which issued it.
+    static NEXT_CACHE_ID: AtomicU64 = AtomicU64::new(0);
+
+    /// Opaque handle to an entry of an LRUCache, reaching the entry without hashing its
+    /// key. A handle goes stale once its entry leaves the cache, even if the slot of the
+    /// entry is reused. Handles issued by another cache are always stale.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct CacheHandle {
+        cache_id: u64,
+        link: Link,
+    }
+
     /// A Least-Recently-Used (LRU) Cache implemented using a generational arena
     /// based linked list and a hash map.
     pub struct LRUCache<K, V>
     where
         K: Eq + Hash,
     {
-        blocks: LinkedList<Block<K, V>>,
+        blocks: LinkedList<Entry<K, V>>,
         block_refs: HashMap<K, Link>,
+        policy: EvictionPolicy,
+        insertion: InsertionPolicy,
+        psel: u32,
+        rng: XorShift64,
+        victims: LinkedList<Entry<K, V>>,
+        victim_refs: HashMap<K, Link>,
+        victim_hits: u64,
+        pinned: HashSet<K>,
+        last_version: u64,
+        id: u64,
+        clock: Option<BudgetClock>,
+        weigher: Option<Weigher<K, V>>,
+        oldest: Option<Link>,
     }
 
     #[derive(Debug, Clone, PartialEq)]
+    #[non_exhaustive]
     pub enum CacheError {
         CacheBroken(ListError),
         CacheMiss,
+        AllEntriesPinned,
+        StaleHandle,
+        VersionMismatch,
+        InvalidPolicy,
     }
 
     impl Display for CacheError {
@@ -988,70 +1389,8294 @@ pub mod lrucache {
                     list_error.fmt(f)
                 }
                 CacheError::CacheMiss => write!(f, "Key not found in cache."),
+                CacheError::AllEntriesPinned => {
+                    write!(f, "Every cache entry is pinned; none can be evicted.")
+                }
+                CacheError::StaleHandle => write!(f, "Handle does not point to a cache entry."),
+                CacheError::VersionMismatch => {
+                    write!(f, "Cache entry changed since the expected version.")
+                }
+                CacheError::InvalidPolicy => {
+                    write!(f, "Insertion probability must be within [0, 1].")
+                }
             }
         }
     }
 
     impl<K, V> LRUCache<K, V>
     where
-        K: Eq + Hash + Copy,
+        K: Eq + Hash + Clone,
     {
         /// Creates an LRUCache instance with the given capacity. A zero capacity LRUCache is
         /// unusable.
         pub fn with_capacity(capacity: usize) -> Self {
+            Self::with_capacity_and_policy(capacity, EvictionPolicy::default())
+        }
+
+        /// Creates an LRUCache instance with the given capacity, evicting entries as per
+        /// the given policy. A zero capacity LRUCache is unusable.
+        pub fn with_capacity_and_policy(capacity: usize, policy: EvictionPolicy) -> Self {
             LRUCache {
                 blocks: LinkedList::with_capacity(capacity),
                 block_refs: HashMap::new(),
+                policy,
+                insertion: InsertionPolicy::default(),
+                psel: PSEL_MAX / 2,
+                rng: XorShift64::with_seed(0x9e37_79b9_7f4a_7c15),
+                victims: LinkedList::with_capacity(0),
+                victim_refs: HashMap::new(),
+                victim_hits: 0,
+                pinned: HashSet::new(),
+                last_version: 0,
+                id: NEXT_CACHE_ID.fetch_add(1, Ordering::Relaxed),
+                clock: None,
+                weigher: None,
+                oldest: None,
             }
         }
 
-        /// Returns a reference to the value associated with the given key. If the key is not
-        /// present in the cache, we return a "cache-miss" error. If the entry is found but
-        /// cannot be fetched from the underlying storage, we return a "cache-broken" error.
-        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
-            let link = self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
-            self.blocks
-                .reposition_to_tail(link)
-                .map_err(CacheError::CacheBroken)?;
-            let node = self.blocks.get(link).map_err(CacheError::CacheBroken)?;
-            Ok(&node.value.value)
+        /// Returns whether an entry for the given key is present in this cache, or in its
+        /// victim buffer, without marking it as used.
+        pub fn contains(&self, key: &K) -> bool {
+            self.block_refs.contains_key(key) || self.victim_refs.contains_key(key)
         }
 
-        /// Removes the associated key value pair for the given key from this cache. If no
-        /// entry is found, we return a "cache-miss" error. If the entry is found but cannot
-        /// be fetched from the underlying in-memory storage, we return a "cache-broken" error.
-        /// Returns the value associated, after removal with ownership.
-        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
-            let link = self.block_refs.remove(key).ok_or(CacheError::CacheMiss)?;
-            let block = self.blocks.remove(&link).map_err(CacheError::CacheBroken)?;
-            Ok(block.value)
+        /// Returns the number of entries in this cache. Unlike [`LRUCache::contains`], this
+        /// leaves out the entries in the victim buffer.
+        pub fn len(&self) -> usize {
+            self.blocks.len()
         }
 
-        /// Inserts a new key value pair into this cache. If this cache is full, the least
-        /// recently used entry is removed.
-        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
-            if let Some(link) = self.block_refs.get(&key) {
+        pub fn is_empty(&self) -> bool {
+            self.blocks.is_empty()
+        }
+
+        pub fn capacity(&self) -> usize {
+            self.blocks.capacity()
+        }
+
+        /// Returns a reference to the value associated with the given key, without marking
+        /// the entry as used or re-admitting it from the victim buffer. If the key is not
+        /// present in the cache, we return a "cache-miss" error.
+        pub fn peek(&self, key: &K) -> Result<&V, CacheError> {
+            let (list, link) = match self.block_refs.get(key) {
+                Some(link) => (&self.blocks, link),
+                None => (
+                    &self.victims,
+                    self.victim_refs.get(key).ok_or(CacheError::CacheMiss)?,
+                ),
+            };
+            let node = list.get(link).map_err(CacheError::CacheBroken)?;
+            Ok(&node.value.block.value)
+        }
+
+        /// Returns a mutable reference to the value associated with the given key, like
+        /// `peek`. Handing out the reference counts as a write, so the entry gets a new
+        /// version, and `insert_if_version` with an earlier one fails.
+        pub fn peek_mut(&mut self, key: &K) -> Result<&mut V, CacheError> {
+            let (list, link) = match self.block_refs.get(key) {
+                Some(link) => (&mut self.blocks, link),
+                None => (
+                    &mut self.victims,
+                    self.victim_refs.get(key).ok_or(CacheError::CacheMiss)?,
+                ),
+            };
+            let entry = list.get_mut_value(link).map_err(CacheError::CacheBroken)?;
+            self.last_version += 1;
+            entry.version = self.last_version;
+            Ok(&mut entry.block.value)
+        }
+
+        /// Returns the least recently used entry of this cache, without marking it as used.
+        pub fn peek_lru(&self) -> Option<(&K, &V)> {
+            let entry = self.blocks.peek_front().ok()?;
+            Some((&entry.block.key, &entry.block.value))
+        }
+
+        /// Iterates over the entries of this cache, from the least to the most recently used
+        /// one, without marking them as used. Entries in the victim buffer are left out.
+        pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+            self.blocks
+                .iter()
+                .map(|entry| (&entry.block.key, &entry.block.value))
+        }
+
+        pub fn policy(&self) -> EvictionPolicy {
+            self.policy
+        }
+
+        pub fn insertion_policy(&self) -> InsertionPolicy {
+            self.insertion
+        }
+
+        /// Sets where newly inserted entries are placed. Entries already present in this
+        /// cache are left where they are. A bimodal or dynamic policy with an epsilon
+        /// outside `[0, 1]` is rejected with an "invalid-policy" error.
+        pub fn set_insertion_policy(
+            &mut self,
+            insertion: InsertionPolicy,
+        ) -> Result<(), CacheError> {
+            if let InsertionPolicy::Bimodal { epsilon } | InsertionPolicy::Dynamic { epsilon } =
+                insertion
+            {
+                if !(0.0..=1.0).contains(&epsilon) {
+                    return Err(CacheError::InvalidPolicy);
+                }
+            }
+
+            self.insertion = insertion;
+            Ok(())
+        }
+
+        /// Keeps up to the given number of entries evicted from this cache in a victim
+        /// buffer, from where a later query re-admits them. A zero capacity disables the
+        /// victim buffer. Entries already in the victim buffer are kept, newest first, as
+        /// long as they fit; older ones are dropped.
+        pub fn set_victim_capacity(&mut self, capacity: usize) {
+            let mut victims =
+                std::mem::replace(&mut self.victims, LinkedList::with_capacity(capacity));
+            self.victim_refs.clear();
+
+            let mut stale = victims.len().saturating_sub(capacity);
+            while let Ok(entry) = victims.pop_front() {
+                if stale > 0 {
+                    stale -= 1;
+                    continue;
+                }
+
+                let key = entry.block.key.clone();
+                if let Ok(link) = self.victims.push_back(entry) {
+                    self.victim_refs.insert(key, link);
+                }
+            }
+        }
+
+        /// Returns the number of queries served from the victim buffer.
+        pub fn victim_hits(&self) -> u64 {
+            self.victim_hits
+        }
+
+        fn bimodal(&mut self, epsilon: f64) -> bool {
+            self.rng.next_f64() < epsilon
+        }
+
+        /// Returns whether an entry for the given key, about to be inserted after a miss,
+        /// goes to the most recently used end. Under the dynamic insertion policy, the miss
+        /// is charged against the leader set of the key, if any.
+        fn inserts_at_mru_end(&mut self, key: &K) -> bool {
+            match self.insertion {
+                InsertionPolicy::MostRecentlyUsed => true,
+                InsertionPolicy::LeastRecentlyUsed => false,
+                InsertionPolicy::Bimodal { epsilon } => self.bimodal(epsilon),
+                InsertionPolicy::Dynamic { epsilon } => {
+                    let mut hasher = DefaultHasher::new();
+                    key.hash(&mut hasher);
+
+                    match hasher.finish() % DUELING_SETS {
+                        0 => {
+                            self.psel = (self.psel + 1).min(PSEL_MAX);
+                            true
+                        }
+                        1 => {
+                            self.psel = self.psel.saturating_sub(1);
+                            self.bimodal(epsilon)
+                        }
+                        _ if self.psel > PSEL_MAX / 2 => self.bimodal(epsilon),
+                        _ => true,
+                    }
+                }
+            }
+        }
+
+        /// Marks the entry pointed to by the given link as the most recently used one,
+        /// unless entries are kept in insertion order.
+        fn touch(&mut self, link: &Link) -> Result<(), CacheError> {
+            if self.policy == EvictionPolicy::FirstInFirstOut {
+                return Ok(());
+            }
+
+            self.blocks
+                .reposition_to_tail(link)
+                .map_err(CacheError::CacheBroken)?;
+            if self.policy == EvictionPolicy::MostRecentlyUsed || self.oldest == Some(*link) {
+                self.oldest = None;
+            }
+
+            if let Some(clock) = &self.clock {
+                let entry = self
+                    .blocks
+                    .get_mut_value(link)
+                    .map_err(CacheError::CacheBroken)?;
+                entry.stamp = clock.tick();
+            }
+
+            Ok(())
+        }
+
+        /// Returns a reference to the value associated with the given key. If the key is not
+        /// present in the cache, we return a "cache-miss" error. If the entry is found but
+        /// cannot be fetched from the underlying storage, we return a "cache-broken" error.
+        /// Entries found in the victim buffer are re-admitted to this cache.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let link = match self.block_refs.get(key).copied() {
+                Some(link) => {
+                    self.touch(&link)?;
+                    link
+                }
+                None => self.readmit_victim(key)?,
+            };
+            let node = self.blocks.get(&link).map_err(CacheError::CacheBroken)?;
+            Ok(&node.value.block.value)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error. If the entry is found but cannot
+        /// be fetched from the underlying in-memory storage, we return a "cache-broken" error.
+        /// Returns the value associated, after removal with ownership.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            if let Some(link) = self.block_refs.remove(key) {
+                self.pinned.remove(key);
+                let entry = self.blocks.remove(&link).map_err(CacheError::CacheBroken)?;
+                return Ok(entry.block.value);
+            }
+
+            let link = self.victim_refs.remove(key).ok_or(CacheError::CacheMiss)?;
+            let entry = self
+                .victims
+                .remove(&link)
+                .map_err(CacheError::CacheBroken)?;
+            Ok(entry.block.value)
+        }
+
+        /// Moves the entry for the given key from the victim buffer back into this cache,
+        /// as the most recently used one.
+        fn readmit_victim(&mut self, key: &K) -> Result<Link, CacheError> {
+            if !self.victim_refs.contains_key(key) {
+                return Err(CacheError::CacheMiss);
+            }
+
+            // keep the entry in the victim buffer if it cannot be re-admitted
+            if self.blocks.is_full() && self.pinned.len() == self.blocks.len() {
+                return Err(CacheError::AllEntriesPinned);
+            }
+
+            let victim_link = self.victim_refs.remove(key).ok_or(CacheError::CacheMiss)?;
+            let entry = self
+                .victims
+                .remove(&victim_link)
+                .map_err(CacheError::CacheBroken)?;
+            self.victim_hits += 1;
+            self.admit(entry, true).map(|(link, _)| link)
+        }
+
+        /// Evicts the entry chosen by the eviction policy of this cache, skipping over
+        /// pinned entries, and moves it to the victim buffer if one is kept. Returns the
+        /// block which left this cache altogether, if any.
+        fn evict(&mut self) -> Result<Option<Block<K, V>>, CacheError> {
+            let entry = if self.pinned.is_empty() {
+                match self.policy {
+                    EvictionPolicy::MostRecentlyUsed => self.blocks.pop_back(),
+                    _ => self.blocks.pop_front(),
+                }
+                .map_err(CacheError::CacheBroken)?
+            } else {
+                self.evict_unpinned()?
+            };
+            self.block_refs.remove(&entry.block.key);
+
+            if self.victims.capacity() == 0 {
+                return Ok(Some(entry.block));
+            }
+
+            let mut dropped = None;
+            if self.victims.is_full() {
+                let victim = self.victims.pop_front().map_err(CacheError::CacheBroken)?;
+                self.victim_refs.remove(&victim.block.key);
+                dropped = Some(victim.block);
+            }
+
+            let key = entry.block.key.clone();
+            let link = self
+                .victims
+                .push_back(entry)
+                .map_err(CacheError::CacheBroken)?;
+            self.victim_refs.insert(key, link);
+
+            Ok(dropped)
+        }
+
+        fn evict_unpinned(&mut self) -> Result<Entry<K, V>, CacheError> {
+            if self.pinned.len() == self.blocks.len() {
+                return Err(CacheError::AllEntriesPinned);
+            }
+
+            let from_back = self.policy == EvictionPolicy::MostRecentlyUsed;
+            let start = if from_back {
+                self.blocks.tail()
+            } else {
+                self.blocks.head()
+            };
+
+            let pinned = &self.pinned;
+            let mut cursor = self.blocks.cursor_mut(start);
+            loop {
+                match cursor.current_mut() {
+                    None => return Err(CacheError::AllEntriesPinned),
+                    Some(entry) if pinned.contains(&entry.block.key) => {
+                        if from_back {
+                            cursor.move_prev();
+                        } else {
+                            cursor.move_next();
+                        }
+                    }
+                    Some(_) => return cursor.remove_current().map_err(CacheError::CacheBroken),
+                }
+            }
+        }
+
+        fn admit(
+            &mut self,
+            mut entry: Entry<K, V>,
+            at_mru_end: bool,
+        ) -> Result<(Link, Option<Block<K, V>>), CacheError> {
+            let dropped = if self.blocks.is_full() {
+                self.evict()?
+            } else {
+                None
+            };
+
+            if let Some(clock) = &self.clock {
+                entry.stamp = clock.tick();
+            }
+
+            if at_mru_end == (self.policy == EvictionPolicy::MostRecentlyUsed) {
+                self.oldest = None;
+            }
+
+            let key = entry.block.key.clone();
+            let link = if at_mru_end {
+                self.blocks.push_back(entry)
+            } else {
+                self.blocks.push_front(entry)
+            }
+            .map_err(CacheError::CacheBroken)?;
+            self.block_refs.insert(key, link);
+
+            Ok((link, dropped))
+        }
+
+        /// Inserts a new key value pair into this cache. If this cache is full, the least
+        /// recently used entry is removed, or the one chosen by the eviction policy of
+        /// this cache. New entries are placed as per the insertion policy of this cache.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            self.insert_versioned(key, value).map(|_| ())
+        }
+
+        /// Inserts a new key value pair into this cache, like `insert`, returning the key
+        /// value pair which left this cache to make room for it, if any. With a victim
+        /// buffer, that is the entry dropped from the victim buffer.
+        pub fn push(&mut self, key: K, value: V) -> Result<Option<(K, V)>, CacheError> {
+            let (_, dropped) = self.insert_dropping(key, value)?;
+            Ok(dropped.map(|block| (block.key, block.value)))
+        }
+
+        /// Inserts the given key value pair, returning the new version of the entry.
+        fn insert_versioned(&mut self, key: K, value: V) -> Result<u64, CacheError> {
+            self.insert_dropping(key, value).map(|(version, _)| version)
+        }
+
+        /// Inserts the given key value pair, returning the new version of the entry and the
+        /// block which left this cache to make room for it, if any. Versions are drawn from
+        /// a counter shared by all entries, so that a key which is removed and inserted
+        /// again never gets back an earlier version.
+        fn insert_dropping(
+            &mut self,
+            key: K,
+            value: V,
+        ) -> Result<(u64, Option<Block<K, V>>), CacheError> {
+            self.last_version += 1;
+            let version = self.last_version;
+
+            if let Some(link) = self.block_refs.get(&key).copied() {
+                self.touch(&link)?;
+                let entry = self
+                    .blocks
+                    .get_mut_value(&link)
+                    .map_err(CacheError::CacheBroken)?;
+                entry.block.value = value;
+                entry.version = version;
+                return Ok((version, None));
+            }
+
+            let dropped = self.insert_new(key, value, version)?;
+            Ok((version, dropped))
+        }
+
+        /// Inserts an entry for a key not present in this cache, returning the block which
+        /// left this cache to make room for it, if any.
+        fn insert_new(
+            &mut self,
+            key: K,
+            value: V,
+            version: u64,
+        ) -> Result<Option<Block<K, V>>, CacheError> {
+            // a stale copy in the victim buffer must not be re-admitted later
+            if let Some(link) = self.victim_refs.remove(&key) {
+                self.victims
+                    .remove(&link)
+                    .map_err(CacheError::CacheBroken)?;
+            }
+
+            let at_mru_end = self.inserts_at_mru_end(&key);
+            let (_, dropped) = self.admit(
+                Entry {
+                    block: Block { key, value },
+                    version,
+                    stamp: 0,
+                },
+                at_mru_end,
+            )?;
+
+            Ok(dropped)
+        }
+
+        /// Returns a reference to the value associated with the given key, like `query`,
+        /// along with its version. The version changes whenever the value is written.
+        pub fn query_versioned(&mut self, key: &K) -> Result<(&V, u64), CacheError> {
+            self.query(key)?;
+            let link = self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            let node = self.blocks.get(link).map_err(CacheError::CacheBroken)?;
+            Ok((&node.value.block.value, node.value.version))
+        }
+
+        /// Inserts a new value for the given key, provided the entry for the key is still
+        /// at the expected version, returning the new version. If the entry has been
+        /// written, evicted or removed since, we return a "version-mismatch" error.
+        pub fn insert_if_version(
+            &mut self,
+            key: K,
+            value: V,
+            expected: u64,
+        ) -> Result<u64, CacheError> {
+            let (list, link) = match self.block_refs.get(&key) {
+                Some(link) => (&self.blocks, link),
+                None => (
+                    &self.victims,
+                    self.victim_refs
+                        .get(&key)
+                        .ok_or(CacheError::VersionMismatch)?,
+                ),
+            };
+            let version = list
+                .get(link)
+                .map_err(CacheError::CacheBroken)?
+                .value
+                .version;
+
+            if version != expected {
+                return Err(CacheError::VersionMismatch);
+            }
+
+            self.insert_versioned(key, value)
+        }
+
+        /// Inserts a new key value pair into this cache, like `insert`, and pins it.
+        pub fn insert_pinned(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            self.insert(key.clone(), value)?;
+            self.pinned.insert(key);
+            Ok(())
+        }
+
+        /// Pins the entry for the given key, so that it is never evicted. Pinned entries
+        /// still count against the capacity of this cache. If no entry is found, we return
+        /// a "cache-miss" error.
+        pub fn pin(&mut self, key: &K) -> Result<(), CacheError> {
+            if !self.block_refs.contains_key(key) {
+                return Err(CacheError::CacheMiss);
+            }
+
+            self.pinned.insert(key.clone());
+            Ok(())
+        }
+
+        /// Unpins the entry for the given key, making it evictable again. If no entry is
+        /// found, we return a "cache-miss" error.
+        pub fn unpin(&mut self, key: &K) -> Result<(), CacheError> {
+            if !self.block_refs.contains_key(key) {
+                return Err(CacheError::CacheMiss);
+            }
+
+            self.pinned.remove(key);
+            self.oldest = None;
+            Ok(())
+        }
+
+        pub fn is_pinned(&self, key: &K) -> bool {
+            self.pinned.contains(key)
+        }
+
+        /// Inserts a new key value pair into this cache, like `insert`, returning a handle
+        /// to the entry.
+        pub fn insert_with_handle(&mut self, key: K, value: V) -> Result<CacheHandle, CacheError> {
+            self.insert(key.clone(), value)?;
+            let link = *self.block_refs.get(&key).ok_or(CacheError::CacheMiss)?;
+            Ok(CacheHandle {
+                cache_id: self.id,
+                link,
+            })
+        }
+
+        /// Returns a handle to the entry for the given key, along with a reference to its
+        /// value, like `query`.
+        pub fn query_with_handle(&mut self, key: &K) -> Result<(CacheHandle, &V), CacheError> {
+            self.query(key)?;
+            let link = *self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            let node = self.blocks.get(&link).map_err(CacheError::CacheBroken)?;
+            let handle = CacheHandle {
+                cache_id: self.id,
+                link,
+            };
+            Ok((handle, &node.value.block.value))
+        }
+
+        /// Returns a reference to the value of the entry the given handle points to. If the
+        /// entry has left this cache, we return a "stale-handle" error.
+        pub fn query_by_handle(&mut self, handle: &CacheHandle) -> Result<&V, CacheError> {
+            if handle.cache_id != self.id || self.blocks.get(&handle.link).is_err() {
+                return Err(CacheError::StaleHandle);
+            }
+
+            self.touch(&handle.link)?;
+            let node = self
+                .blocks
+                .get(&handle.link)
+                .map_err(CacheError::CacheBroken)?;
+            Ok(&node.value.block.value)
+        }
+
+        /// Removes the entry the given handle points to from this cache, returning its
+        /// value. If the entry has left this cache, we return a "stale-handle" error.
+        pub fn remove_by_handle(&mut self, handle: &CacheHandle) -> Result<V, CacheError> {
+            if handle.cache_id != self.id {
+                return Err(CacheError::StaleHandle);
+            }
+
+            let entry = self
+                .blocks
+                .remove(&handle.link)
+                .map_err(|_| CacheError::StaleHandle)?;
+            self.block_refs.remove(&entry.block.key);
+            self.pinned.remove(&entry.block.key);
+            Ok(entry.block.value)
+        }
+
+        /// Queries the given keys in order, returning a result per key. All promotions are
+        /// applied before any value is read back. An entry re-admitted from the victim
+        /// buffer may evict an entry queried earlier in the same batch, which is then
+        /// reported as a miss.
+        pub fn get_many<'a, I>(&mut self, keys: I) -> Vec<Result<&V, CacheError>>
+        where
+            I: IntoIterator<Item = &'a K>,
+            K: 'a,
+        {
+            let links: Vec<_> = keys
+                .into_iter()
+                .map(|key| match self.block_refs.get(key).copied() {
+                    Some(link) => self.touch(&link).map(|_| link),
+                    None => self.readmit_victim(key),
+                })
+                .collect();
+
+            links
+                .into_iter()
+                .map(|link| {
+                    let link = link?;
+                    let node = self.blocks.get(&link).map_err(|_| CacheError::CacheMiss)?;
+                    Ok(&node.value.block.value)
+                })
+                .collect()
+        }
+
+        /// Inserts the given key value pairs in order, returning a result per pair. This
+        /// cache ends up as if `insert` was called for each pair in turn, so a key repeating
+        /// within the batch keeps its last value.
+        ///
+        /// Under the LRU and FIFO eviction policies with MRU insertion, the entries which
+        /// would be evicted before being written to by the batch are evicted in a single
+        /// pass up front. Any further evictions happen as the pairs are inserted.
+        pub fn insert_many<I>(&mut self, pairs: I) -> Vec<Result<(), CacheError>>
+        where
+            I: IntoIterator<Item = (K, V)>,
+        {
+            let pairs: Vec<(K, V)> = pairs.into_iter().collect();
+
+            if self.policy != EvictionPolicy::MostRecentlyUsed
+                && self.insertion == InsertionPolicy::MostRecentlyUsed
+            {
+                let evictions = self.batch_evictions(&pairs);
+                for _ in 0..evictions {
+                    if self.evict().is_err() {
+                        break;
+                    }
+                }
+            }
+
+            pairs
+                .into_iter()
+                .map(|(key, value)| self.insert_versioned(key, value).map(|_| ()))
+                .collect()
+        }
+
+        /// Replays the given pairs against the unpinned entries from the front of this
+        /// cache, returning how many of them inserting the pairs one by one would evict
+        /// before any of them is written to by the pairs.
+        fn batch_evictions(&self, pairs: &[(K, V)]) -> usize {
+            let mut first_writes: HashMap<&K, usize> = HashMap::new();
+            for (position, (key, _)) in pairs.iter().enumerate() {
+                first_writes.entry(key).or_insert(position);
+            }
+
+            let mut free = self.blocks.capacity() - self.blocks.len();
+            let mut residents = self
+                .blocks
+                .iter()
+                .map(|entry| &entry.block.key)
+                .filter(|key| !self.pinned.contains(*key));
+            let mut evicted = HashSet::new();
+            let mut inserted = HashSet::new();
+
+            for (position, (key, _)) in pairs.iter().enumerate() {
+                let present = self.block_refs.contains_key(key) && !evicted.contains(key);
+                if present || !inserted.insert(key) {
+                    continue;
+                }
+
+                if free > 0 {
+                    free -= 1;
+                    continue;
+                }
+
+                match residents.next() {
+                    Some(victim) if first_writes.get(victim).map_or(true, |&w| w > position) => {
+                        evicted.insert(victim);
+                    }
+                    _ => break,
+                }
+            }
+
+            evicted.len()
+        }
+
+        /// Removes the entries for the given keys in order, returning a result per key.
+        pub fn remove_many<'a, I>(&mut self, keys: I) -> Vec<Result<V, CacheError>>
+        where
+            I: IntoIterator<Item = &'a K>,
+            K: 'a,
+        {
+            keys.into_iter().map(|key| self.remove(key)).collect()
+        }
+
+        /// Removes the blocks matching the given predicate from the given list, along with
+        /// their links, appending them to the removed pairs.
+        fn drain_matching<F>(
+            blocks: &mut LinkedList<Entry<K, V>>,
+            block_refs: &mut HashMap<K, Link>,
+            predicate: &mut F,
+            removed: &mut Vec<(K, V)>,
+        ) -> Result<(), CacheError>
+        where
+            F: FnMut(&K, &V) -> bool,
+        {
+            let head = blocks.head();
+            let mut cursor = blocks.cursor_mut(head);
+            while let Some(Entry { block, .. }) = cursor.current_mut() {
+                if !predicate(&block.key, &block.value) {
+                    cursor.move_next();
+                    continue;
+                }
+
+                let Entry { block, .. } =
+                    cursor.remove_current().map_err(CacheError::CacheBroken)?;
+                block_refs.remove(&block.key);
+                removed.push((block.key, block.value));
+            }
+
+            Ok(())
+        }
+
+        /// Removes every entry for which the given predicate returns true, including those
+        /// in the victim buffer. Returns the removed key value pairs, from the least to the
+        /// most recently used one.
+        pub fn remove_if<F>(&mut self, mut predicate: F) -> Result<Vec<(K, V)>, CacheError>
+        where
+            F: FnMut(&K, &V) -> bool,
+        {
+            let mut removed = Vec::new();
+            Self::drain_matching(
+                &mut self.victims,
+                &mut self.victim_refs,
+                &mut predicate,
+                &mut removed,
+            )?;
+            Self::drain_matching(
+                &mut self.blocks,
+                &mut self.block_refs,
+                &mut predicate,
+                &mut removed,
+            )?;
+
+            for (key, _) in &removed {
+                self.pinned.remove(key);
+            }
+
+            Ok(removed)
+        }
+
+        /// Keeps only the entries for which the given predicate returns true, including
+        /// those in the victim buffer. Returns the removed key value pairs, from the least to
+        /// the most recently used one.
+        pub fn retain<F>(&mut self, mut predicate: F) -> Result<Vec<(K, V)>, CacheError>
+        where
+            F: FnMut(&K, &V) -> bool,
+        {
+            self.remove_if(|key, value| !predicate(key, value))
+        }
+    }
+
+    impl<K, V> LRUCache<K, V>
+    where
+        K: Eq + Hash + Clone,
+    {
+        /// Sets how much each entry weighs against the weight budget of a
+        /// BudgetCoordinator. Without a weigher, every entry weighs one.
+        pub fn set_weigher<F>(&mut self, weigher: F)
+        where
+            F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+        {
+            self.weigher = Some(Box::new(weigher));
+        }
+
+        fn weigh(&self, block: &Block<K, V>) -> usize {
+            self.weigher
+                .as_ref()
+                .map_or(1, |weigher| weigher(&block.key, &block.value))
+        }
+
+        /// Returns the link of the entry the eviction policy of this cache would evict,
+        /// skipping over pinned entries. The link is remembered until entries are
+        /// re-arranged in front of it, so that pinned entries are not walked every time.
+        fn oldest_unpinned(&mut self) -> Option<Link> {
+            if let Some(link) = self.oldest {
+                match self.blocks.get(&link) {
+                    Ok(node) if !self.pinned.contains(&node.value.block.key) => return Some(link),
+                    _ => self.oldest = None,
+                }
+            }
+
+            let from_back = self.policy == EvictionPolicy::MostRecentlyUsed;
+            let start = if from_back {
+                self.blocks.tail()
+            } else {
+                self.blocks.head()
+            };
+
+            let pinned = &self.pinned;
+            let mut cursor = self.blocks.cursor_mut(start);
+            while let Some(entry) = cursor.current_mut() {
+                if !pinned.contains(&entry.block.key) {
+                    self.oldest = cursor.current();
+                    return self.oldest;
+                }
+
+                if from_back {
+                    cursor.move_prev();
+                } else {
+                    cursor.move_next();
+                }
+            }
+
+            None
+        }
+    }
+
+    /// Entries of an LRUCache weigh one, unless a weigher is set. Entries in the victim
+    /// buffer count against the budget too, and are evicted first. Otherwise, the entry
+    /// chosen by the eviction policy is evicted, skipping the victim buffer. Pinned
+    /// entries are never evicted.
+    impl<K, V> BudgetMember for LRUCache<K, V>
+    where
+        K: Eq + Hash + Clone,
+    {
+        fn set_clock(&mut self, clock: BudgetClock) {
+            self.clock = Some(clock);
+        }
+
+        fn oldest_stamp(&mut self) -> Option<u64> {
+            if let Some(victim) = self.victims.iter().next() {
+                return Some(victim.stamp);
+            }
+
+            let link = self.oldest_unpinned()?;
+            self.blocks.get(&link).ok().map(|node| node.value.stamp)
+        }
+
+        fn evict_oldest(&mut self) -> Result<usize, CacheError> {
+            if !self.victims.is_empty() {
+                let victim = self.victims.pop_front().map_err(CacheError::CacheBroken)?;
+                self.victim_refs.remove(&victim.block.key);
+                return Ok(self.weigh(&victim.block));
+            }
+
+            if self.blocks.is_empty() {
+                return Err(CacheError::CacheBroken(ListError::ListEmpty));
+            }
+
+            let link = self.oldest_unpinned().ok_or(CacheError::AllEntriesPinned)?;
+            let entry = self.blocks.remove(&link).map_err(CacheError::CacheBroken)?;
+            self.block_refs.remove(&entry.block.key);
+            self.oldest = None;
+            Ok(self.weigh(&entry.block))
+        }
+
+        fn entries(&self) -> usize {
+            self.blocks.len() + self.victims.len()
+        }
+
+        fn weight(&self) -> usize {
+            match self.weigher {
+                None => self.entries(),
+                Some(_) => self
+                    .blocks
+                    .iter()
+                    .chain(self.victims.iter())
+                    .map(|entry| self.weigh(&entry.block))
+                    .sum(),
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn lru_cache_consistency() {
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(0);
+            assert_eq!(
+                lru_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(2);
+            assert!(lru_cache.insert(1, 1).is_ok());
+            assert!(lru_cache.insert(2, 2).is_ok());
+            assert_eq!(lru_cache.query(&1), Ok(&1));
+            assert!(lru_cache.insert(3, 3).is_ok());
+            assert_eq!(lru_cache.query(&2), Err(CacheError::CacheMiss));
+            assert!(lru_cache.insert(1, -1).is_ok());
+            assert_eq!(lru_cache.query(&1), Ok(&-1));
+            assert!(lru_cache.insert(4, 4).is_ok());
+            assert_eq!(lru_cache.query(&3), Err(CacheError::CacheMiss));
+
+            let capacity = 5;
+
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(capacity);
+            assert_eq!(lru_cache.query(&0), Err(CacheError::CacheMiss));
+
+            for ele in 0..capacity {
+                let x = ele as i32;
+                assert!(lru_cache.insert(x, x).is_ok());
+            }
+
+            for ele in 0..capacity {
+                let x = ele as i32;
+                assert_eq!(lru_cache.query(&x), Ok(&x));
+            }
+
+            let x = capacity as i32;
+            assert!(lru_cache.insert(x, x).is_ok());
+
+            assert_eq!(lru_cache.query(&x), Ok(&x));
+
+            assert_eq!(lru_cache.query(&0), Err(CacheError::CacheMiss));
+
+            let x = capacity as i32 / 2;
+            assert_eq!(lru_cache.remove(&x), Ok(x));
+
+            assert_eq!(lru_cache.query(&x), Err(CacheError::CacheMiss));
+            assert_eq!(lru_cache.remove(&x), Err(CacheError::CacheMiss));
+        }
+
+        #[test]
+        fn lru_cache_push() {
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(0);
+            assert_eq!(
+                lru_cache.push(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+            assert_eq!((lru_cache.len(), lru_cache.capacity()), (0, 0));
+
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(2);
+            assert_eq!(lru_cache.peek(&0), Err(CacheError::CacheMiss));
+            assert_eq!(lru_cache.push(0, 0), Ok(None));
+            assert_eq!(lru_cache.push(1, 1), Ok(None));
+            assert_eq!((lru_cache.len(), lru_cache.capacity()), (2, 2));
+
+            // pushing reports the entry making room, peeking does not mark entries as used
+            assert_eq!(lru_cache.peek(&0), Ok(&0));
+            assert_eq!(lru_cache.push(2, 2), Ok(Some((0, 0))));
+            assert_eq!(lru_cache.peek(&0), Err(CacheError::CacheMiss));
+
+            // updates and repeated keys drop nothing
+            assert_eq!(lru_cache.push(2, -2), Ok(None));
+            assert_eq!(lru_cache.push(2, -2), Ok(None));
+            assert_eq!(lru_cache.peek(&2), Ok(&-2));
+            assert_eq!((lru_cache.len(), lru_cache.capacity()), (2, 2));
+
+            // with a victim buffer, the entry dropped from it is reported
+            lru_cache.set_victim_capacity(1);
+            assert_eq!(lru_cache.push(3, 3), Ok(None));
+            assert_eq!(lru_cache.push(4, 4), Ok(Some((1, 1))));
+            assert_eq!(lru_cache.peek(&2), Ok(&-2));
+        }
+
+        #[test]
+        fn lru_cache_eviction_policies() {
+            let mut mru_cache =
+                LRUCache::<i32, i32>::with_capacity_and_policy(3, EvictionPolicy::MostRecentlyUsed);
+            assert_eq!(mru_cache.policy(), EvictionPolicy::MostRecentlyUsed);
+            for ele in 0..3 {
+                assert!(mru_cache.insert(ele, ele).is_ok());
+            }
+            assert_eq!(mru_cache.query(&0), Ok(&0));
+
+            // 0 was used most recently
+            assert!(mru_cache.insert(3, 3).is_ok());
+            assert_eq!(mru_cache.query(&0), Err(CacheError::CacheMiss));
+
+            // 3 was inserted most recently
+            assert!(mru_cache.insert(4, 4).is_ok());
+            assert_eq!(mru_cache.query(&3), Err(CacheError::CacheMiss));
+            assert!(mru_cache.blocks.iter().map(|b| b.block.key).eq([1, 2, 4]));
+
+            let mut fifo_cache =
+                LRUCache::<i32, i32>::with_capacity_and_policy(3, EvictionPolicy::FirstInFirstOut);
+            for ele in 0..3 {
+                assert!(fifo_cache.insert(ele, ele).is_ok());
+            }
+            assert_eq!(fifo_cache.query(&0), Ok(&0));
+            assert!(fifo_cache.insert(1, -1).is_ok());
+
+            // hits and updates leave the insertion order alone
+            assert!(fifo_cache.insert(3, 3).is_ok());
+            assert_eq!(fifo_cache.query(&0), Err(CacheError::CacheMiss));
+            assert!(fifo_cache.insert(4, 4).is_ok());
+            assert_eq!(fifo_cache.query(&1), Err(CacheError::CacheMiss));
+            assert!(fifo_cache.blocks.iter().map(|b| b.block.key).eq([2, 3, 4]));
+            assert_eq!(fifo_cache.remove(&3), Ok(3));
+
+            let lru_cache = LRUCache::<i32, i32>::with_capacity(1);
+            assert_eq!(lru_cache.policy(), EvictionPolicy::LeastRecentlyUsed);
+        }
+
+        #[test]
+        fn lru_cache_insertion_policies() {
+            let mut lip_cache = LRUCache::<i32, i32>::with_capacity(3);
+            assert_eq!(
+                lip_cache.insertion_policy(),
+                InsertionPolicy::MostRecentlyUsed
+            );
+            assert!(lip_cache
+                .set_insertion_policy(InsertionPolicy::LeastRecentlyUsed)
+                .is_ok());
+            for ele in 0..3 {
+                assert!(lip_cache.insert(ele, ele).is_ok());
+            }
+            assert!(lip_cache.blocks.iter().map(|b| b.block.key).eq([2, 1, 0]));
+            assert_eq!(lip_cache.query(&2), Ok(&2));
+
+            // new entries are evicted first unless hit
+            assert!(lip_cache.insert(3, 3).is_ok());
+            assert!(lip_cache.insert(4, 4).is_ok());
+            assert_eq!(lip_cache.query(&3), Err(CacheError::CacheMiss));
+            assert!(lip_cache.blocks.iter().map(|b| b.block.key).eq([4, 0, 2]));
+
+            let mut bip_cache = LRUCache::<i32, i32>::with_capacity(3);
+            assert!(bip_cache
+                .set_insertion_policy(InsertionPolicy::Bimodal { epsilon: 1.0 })
+                .is_ok());
+            for ele in 0..3 {
+                assert!(bip_cache.insert(ele, ele).is_ok());
+            }
+            assert!(bip_cache.blocks.iter().map(|b| b.block.key).eq([0, 1, 2]));
+            assert!(bip_cache
+                .set_insertion_policy(InsertionPolicy::Bimodal { epsilon: 0.0 })
+                .is_ok());
+            assert!(bip_cache.insert(3, 3).is_ok());
+            assert!(bip_cache.blocks.iter().map(|b| b.block.key).eq([3, 1, 2]));
+
+            let set_of = |key: i32| {
+                let mut hasher = DefaultHasher::new();
+                key.hash(&mut hasher);
+                hasher.finish() % DUELING_SETS
+            };
+            let keys_in = |set: u64| (0..).filter(move |&key| set_of(key) == set);
+            let follower = keys_in(2).next().unwrap();
+
+            let mut dip_cache = LRUCache::<i32, i32>::with_capacity(3);
+            assert!(dip_cache
+                .set_insertion_policy(InsertionPolicy::Dynamic { epsilon: 0.0 })
+                .is_ok());
+
+            // misses on the MRU leader set make followers insert bimodally
+            for key in keys_in(0).take(PSEL_MAX as usize) {
+                assert!(dip_cache.insert(key, key).is_ok());
+            }
+            assert_eq!(dip_cache.psel, PSEL_MAX);
+            assert!(dip_cache.insert(follower, follower).is_ok());
+            assert_eq!(
+                dip_cache.blocks.iter().next().map(|b| b.block.key),
+                Some(follower)
+            );
+
+            // misses on the bimodal leader set make followers insert at the MRU end
+            for key in keys_in(1).take(PSEL_MAX as usize) {
+                assert!(dip_cache.insert(key, key).is_ok());
+            }
+            assert_eq!(dip_cache.psel, 0);
+            assert_eq!(dip_cache.query(&follower), Err(CacheError::CacheMiss));
+            assert!(dip_cache.insert(follower, follower).is_ok());
+            assert_eq!(
+                dip_cache.blocks.iter().last().map(|b| b.block.key),
+                Some(follower)
+            );
+
+            // epsilon has to be a probability, and rejected policies are not applied
+            for epsilon in [-0.5, 1.5, f64::NAN] {
+                assert_eq!(
+                    dip_cache.set_insertion_policy(InsertionPolicy::Bimodal { epsilon }),
+                    Err(CacheError::InvalidPolicy)
+                );
+                assert_eq!(
+                    dip_cache.set_insertion_policy(InsertionPolicy::Dynamic { epsilon }),
+                    Err(CacheError::InvalidPolicy)
+                );
+            }
+            assert_eq!(
+                dip_cache.insertion_policy(),
+                InsertionPolicy::Dynamic { epsilon: 0.0 }
+            );
+        }
+
+        #[test]
+        fn lru_cache_victim_buffer() {
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(2);
+            assert!(lru_cache.insert(0, 0).is_ok());
+            assert!(lru_cache.insert(1, 1).is_ok());
+            assert!(lru_cache.insert(2, 2).is_ok());
+            assert_eq!(lru_cache.query(&0), Err(CacheError::CacheMiss));
+
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(2);
+            lru_cache.set_victim_capacity(2);
+            for ele in 0..4 {
+                assert!(lru_cache.insert(ele, ele).is_ok());
+            }
+            assert!(lru_cache.victims.iter().map(|b| b.block.key).eq([0, 1]));
+
+            // re-admitting 0 evicts 2 into the victim buffer, pushing out 1
+            assert_eq!(lru_cache.query(&0), Ok(&0));
+            assert_eq!(lru_cache.victim_hits(), 1);
+            assert!(lru_cache.blocks.iter().map(|b| b.block.key).eq([3, 0]));
+            assert!(lru_cache.victims.iter().map(|b| b.block.key).eq([1, 2]));
+
+            // updates and removals reach entries in the victim buffer
+            assert!(lru_cache.insert(2, -2).is_ok());
+            assert_eq!(lru_cache.remove(&3), Ok(3));
+            assert!(lru_cache.victims.iter().map(|b| b.block.key).eq([1]));
+            assert_eq!(lru_cache.remove(&1), Ok(1));
+            assert_eq!(lru_cache.query(&1), Err(CacheError::CacheMiss));
+            assert_eq!(lru_cache.query(&2), Ok(&-2));
+            assert_eq!(lru_cache.victim_hits(), 1);
+
+            // shrinking the victim buffer keeps the newest victims
+            assert!(lru_cache.insert(6, 6).is_ok());
+            assert!(lru_cache.insert(7, 7).is_ok());
+            assert!(lru_cache.victims.iter().map(|b| b.block.key).eq([0, 2]));
+            lru_cache.set_victim_capacity(1);
+            assert!(lru_cache.victims.iter().map(|b| b.block.key).eq([2]));
+            assert!(!lru_cache.contains(&0) && lru_cache.contains(&2));
+            assert_eq!(lru_cache.len(), 2);
+            lru_cache.set_victim_capacity(2);
+            assert_eq!(lru_cache.query(&2), Ok(&-2));
+            assert!(lru_cache.victims.iter().map(|b| b.block.key).eq([6]));
+
+            lru_cache.set_victim_capacity(0);
+            assert!(lru_cache.insert(4, 4).is_ok());
+            assert!(lru_cache.insert(5, 5).is_ok());
+            assert_eq!(lru_cache.query(&0), Err(CacheError::CacheMiss));
+            assert!(lru_cache.victims.is_empty());
+        }
+
+        #[test]
+        fn lru_cache_handles() {
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(2);
+            let zero = lru_cache.insert_with_handle(0, 0).unwrap();
+            assert!(lru_cache.insert(1, 1).is_ok());
+            assert_eq!(lru_cache.query_by_handle(&zero), Ok(&0));
+
+            // updates keep handles valid, and handles keep entries recently used
+            let one = lru_cache.insert_with_handle(1, -1).unwrap();
+            assert_eq!(lru_cache.query_by_handle(&zero), Ok(&0));
+            assert!(lru_cache.insert(2, 2).is_ok());
+            assert_eq!(
+                lru_cache.query_by_handle(&one),
+                Err(CacheError::StaleHandle)
+            );
+
+            let (two, value) = lru_cache.query_with_handle(&2).unwrap();
+            assert_eq!(value, &2);
+            assert_eq!(lru_cache.remove_by_handle(&two), Ok(2));
+            assert_eq!(
+                lru_cache.remove_by_handle(&two),
+                Err(CacheError::StaleHandle)
+            );
+            assert_eq!(lru_cache.query(&2), Err(CacheError::CacheMiss));
+
+            // the slot of 2 is reused, but its handle stays stale
+            assert!(lru_cache.insert(3, 3).is_ok());
+            assert_eq!(
+                lru_cache.query_by_handle(&two),
+                Err(CacheError::StaleHandle)
+            );
+            assert_eq!(lru_cache.remove_by_handle(&zero), Ok(0));
+            assert!(lru_cache.blocks.iter().map(|b| b.block.key).eq([3]));
+            assert_eq!(lru_cache.query_with_handle(&0), Err(CacheError::CacheMiss));
+
+            // a handle is only honoured by the cache which issued it
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(2);
+            let mut other_cache = LRUCache::<i32, i32>::with_capacity(2);
+            let zero = lru_cache.insert_with_handle(0, 0).unwrap();
+            let four = other_cache.insert_with_handle(4, 4).unwrap();
+            assert_eq!(zero.link, four.link);
+            assert_eq!(
+                other_cache.query_by_handle(&zero),
+                Err(CacheError::StaleHandle)
+            );
+            assert_eq!(
+                other_cache.remove_by_handle(&zero),
+                Err(CacheError::StaleHandle)
+            );
+            assert_eq!(other_cache.query(&4), Ok(&4));
+        }
+
+        #[test]
+        fn lru_cache_versioned_updates() {
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(2);
+            lru_cache.set_victim_capacity(1);
+            assert_eq!(lru_cache.query_versioned(&0), Err(CacheError::CacheMiss));
+            assert!(lru_cache.insert(0, 0).is_ok());
+            let (_, version) = lru_cache.query_versioned(&0).unwrap();
+
+            // the first of two writers expecting the same version wins
+            let new_version = lru_cache.insert_if_version(0, 1, version).unwrap();
+            assert_eq!(
+                lru_cache.insert_if_version(0, 2, version),
+                Err(CacheError::VersionMismatch)
+            );
+            assert_eq!(lru_cache.query_versioned(&0), Ok((&1, new_version)));
+
+            // entries in the victim buffer keep their version
+            assert!(lru_cache.insert(1, 1).is_ok());
+            assert!(lru_cache.insert(2, 2).is_ok());
+            assert!(lru_cache.victims.iter().map(|b| b.block.key).eq([0]));
+            assert!(lru_cache.insert_if_version(0, 3, new_version).is_ok());
+            assert_eq!(lru_cache.query(&0), Ok(&3));
+
+            // a removed and re-inserted entry never gets back an earlier version
+            let (_, version) = lru_cache.query_versioned(&2).unwrap();
+            assert_eq!(lru_cache.remove(&2), Ok(2));
+            assert_eq!(
+                lru_cache.insert_if_version(2, 4, version),
+                Err(CacheError::VersionMismatch)
+            );
+            assert!(lru_cache.insert(2, 2).is_ok());
+            assert_eq!(
+                lru_cache.insert_if_version(2, 4, version),
+                Err(CacheError::VersionMismatch)
+            );
+            assert_eq!(lru_cache.query(&2), Ok(&2));
+        }
+
+        #[test]
+        fn lru_cache_peek_mut() {
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(2);
+            lru_cache.set_victim_capacity(1);
+            assert_eq!(lru_cache.peek_mut(&0), Err(CacheError::CacheMiss));
+            assert_eq!(lru_cache.peek_lru(), None);
+            assert!(lru_cache.insert(0, 0).is_ok());
+            assert!(lru_cache.insert(1, 1).is_ok());
+
+            // writing through peek_mut does not mark the entry as used
+            *lru_cache.peek_mut(&0).unwrap() = -1;
+            assert_eq!(lru_cache.peek_lru(), Some((&0, &-1)));
+            assert!(lru_cache.iter().eq([(&0, &-1), (&1, &1)]));
+
+            // entries in the victim buffer are reached, but not iterated over
+            assert!(lru_cache.insert(2, 2).is_ok());
+            *lru_cache.peek_mut(&0).unwrap() = -2;
+            assert_eq!(lru_cache.peek(&0), Ok(&-2));
+            assert!(lru_cache.iter().eq([(&1, &1), (&2, &2)]));
+
+            // peek_mut hands out a new version, like any other write
+            let (_, version) = lru_cache.query_versioned(&1).unwrap();
+            *lru_cache.peek_mut(&1).unwrap() = -1;
+            assert_eq!(
+                lru_cache.insert_if_version(1, 10, version),
+                Err(CacheError::VersionMismatch)
+            );
+            assert_eq!(lru_cache.query(&1), Ok(&-1));
+        }
+
+        #[test]
+        fn lru_cache_batch_operations() {
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(4);
+            let results = lru_cache.insert_many((0..3).map(|ele| (ele, ele)));
+            assert_eq!(results, [Ok(()), Ok(()), Ok(())]);
+
+            let results = lru_cache.get_many(&[2, 5, 0]);
+            assert_eq!(results, [Ok(&2), Err(CacheError::CacheMiss), Ok(&0)]);
+            assert!(lru_cache.blocks.iter().map(|b| b.block.key).eq([1, 2, 0]));
+
+            // pairs apply in order, with 1 and 2 making room for 4 and 5
+            let results = lru_cache.insert_many([(3, 3), (0, -1), (4, 4), (3, -3), (5, 5)]);
+            assert!(results.iter().all(Result::is_ok));
+            assert!(lru_cache
+                .blocks
+                .iter()
+                .map(|b| b.block.key)
+                .eq([0, 4, 3, 5]));
+            assert_eq!(lru_cache.get_many(&[0, 3]), [Ok(&-1), Ok(&-3)]);
+
+            // new entries beyond the capacity push each other out, as single inserts would
+            let results = lru_cache.insert_many((6..12).map(|ele| (ele, ele)));
+            assert_eq!(results.len(), 6);
+            assert!(results.iter().all(Result::is_ok));
+            assert!(lru_cache.blocks.iter().map(|b| b.block.key).eq(8..12));
+
+            let results = lru_cache.remove_many(&[8, 8, 9]);
+            assert_eq!(results, [Ok(8), Err(CacheError::CacheMiss), Ok(9)]);
+            assert_eq!(lru_cache.blocks.len(), 2);
+
+            // eviction stops at pinned entries
+            assert!(lru_cache.pin(&10).is_ok());
+            assert!(lru_cache.pin(&11).is_ok());
+            assert!(lru_cache
+                .insert_many([(0, 0), (1, 1)])
+                .iter()
+                .all(Result::is_ok));
+            // 0 is evicted by 2 before being written to, and evicts 1 in turn
+            let results = lru_cache.insert_many([(2, 2), (0, -1), (3, 3)]);
+            assert!(lru_cache
+                .blocks
+                .iter()
+                .map(|b| b.block.key)
+                .eq([10, 11, 0, 3]));
+            assert_eq!(results, [Ok(()), Ok(()), Ok(())]);
+            let results = lru_cache.insert_many([(4, 4), (5, 5), (6, 6)]);
+            assert!(results.iter().all(Result::is_ok));
+            assert!(lru_cache
+                .blocks
+                .iter()
+                .map(|b| b.block.key)
+                .eq([10, 11, 5, 6]));
+            assert!(lru_cache.pin(&5).is_ok());
+            assert!(lru_cache.pin(&6).is_ok());
+            let results = lru_cache.insert_many([(5, -5), (7, 7), (7, -7)]);
+            assert_eq!(
+                results,
+                [
+                    Ok(()),
+                    Err(CacheError::AllEntriesPinned),
+                    Err(CacheError::AllEntriesPinned)
+                ]
+            );
+
+            // other policies end up as if the pairs were inserted one by one
+            let policies = [
+                (
+                    EvictionPolicy::MostRecentlyUsed,
+                    InsertionPolicy::MostRecentlyUsed,
+                ),
+                (
+                    EvictionPolicy::LeastRecentlyUsed,
+                    InsertionPolicy::LeastRecentlyUsed,
+                ),
+            ];
+            for (policy, insertion) in policies {
+                let mut batch_cache = LRUCache::<i32, i32>::with_capacity_and_policy(3, policy);
+                let mut single_cache = LRUCache::<i32, i32>::with_capacity_and_policy(3, policy);
+                assert!(batch_cache.set_insertion_policy(insertion).is_ok());
+                assert!(single_cache.set_insertion_policy(insertion).is_ok());
+
+                assert!(batch_cache
+                    .insert_many((0..5).map(|ele| (ele, ele)))
+                    .iter()
+                    .all(Result::is_ok));
+                for ele in 0..5 {
+                    assert!(single_cache.insert(ele, ele).is_ok());
+                }
+                assert!(batch_cache
+                    .blocks
+                    .iter()
+                    .map(|b| b.block.key)
+                    .eq(single_cache.blocks.iter().map(|b| b.block.key)));
+            }
+        }
+
+        #[test]
+        fn lru_cache_batch_order() {
+            let batches = [
+                vec![(3, 3), (4, 4), (1, 100)],
+                vec![(1, 100), (3, 3), (4, 4)],
+                vec![(3, 3), (3, -3), (2, 20), (4, 4), (3, 30)],
+                vec![(0, 0), (1, 10), (0, -1), (5, 5), (1, 11), (6, 6)],
+            ];
+            let policies = [
+                EvictionPolicy::LeastRecentlyUsed,
+                EvictionPolicy::FirstInFirstOut,
+            ];
+
+            for policy in policies {
+                for batch in &batches {
+                    for pinned in [None, Some(2)] {
+                        let mut batch_cache = LRUCache::with_capacity_and_policy(3, policy);
+                        let mut single_cache = LRUCache::with_capacity_and_policy(3, policy);
+                        for cache in [&mut batch_cache, &mut single_cache] {
+                            cache.set_victim_capacity(1);
+                            for ele in 0..3 {
+                                assert!(cache.insert(ele, ele).is_ok());
+                            }
+                            if let Some(key) = pinned {
+                                assert!(cache.pin(&key).is_ok());
+                            }
+                        }
+
+                        let results = batch_cache.insert_many(batch.iter().copied());
+                        for (&(key, value), result) in batch.iter().zip(results) {
+                            assert_eq!(single_cache.insert(key, value), result);
+                        }
+
+                        let entries = |cache: &LRUCache<i32, i32>| {
+                            cache
+                                .blocks
+                                .iter()
+                                .chain(cache.victims.iter())
+                                .map(|b| (b.block.key, b.block.value))
+                                .collect::<Vec<_>>()
+                        };
+                        assert_eq!(entries(&batch_cache), entries(&single_cache));
+                    }
+                }
+            }
+
+            // the updated entry survives the batch, as it would single inserts
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(2);
+            assert!(lru_cache
+                .insert_many([(1, 1), (2, 2)])
+                .iter()
+                .all(Result::is_ok));
+            let results = lru_cache.insert_many([(3, 3), (4, 4), (1, 100)]);
+            assert!(results.iter().all(Result::is_ok));
+            assert!(lru_cache.iter().eq([(&4, &4), (&1, &100)]));
+        }
+
+        #[test]
+        fn lru_cache_predicate_removal() {
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(4);
+            lru_cache.set_victim_capacity(2);
+            for ele in 0..6 {
+                assert!(lru_cache.insert(ele, ele * 10).is_ok());
+            }
+            assert!(lru_cache.pin(&4).is_ok());
+            assert_eq!(lru_cache.query(&2), Ok(&20));
+
+            let removed = lru_cache.remove_if(|key, _| key % 2 == 0).unwrap();
+            assert_eq!(removed, [(0, 0), (4, 40), (2, 20)]);
+            assert!(lru_cache.blocks.iter().map(|b| b.block.key).eq([3, 5]));
+            assert!(lru_cache.victims.iter().map(|b| b.block.key).eq([1]));
+            assert!(!lru_cache.is_pinned(&4));
+            assert_eq!(lru_cache.query(&2), Err(CacheError::CacheMiss));
+
+            let removed = lru_cache.retain(|_, value| *value > 10).unwrap();
+            assert_eq!(removed, [(1, 10)]);
+            assert_eq!(lru_cache.query(&1), Err(CacheError::CacheMiss));
+            assert_eq!(lru_cache.retain(|_, _| true), Ok(Vec::new()));
+
+            let removed = lru_cache.retain(|_, _| false).unwrap();
+            assert_eq!(removed, [(3, 30), (5, 50)]);
+            assert!(lru_cache.blocks.is_empty());
+            assert!(lru_cache.block_refs.is_empty());
+            assert!(lru_cache.insert(6, 60).is_ok());
+            assert_eq!(lru_cache.query(&6), Ok(&60));
+        }
+
+        #[test]
+        fn lru_cache_pinned_entries() {
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(3);
+            assert_eq!(lru_cache.pin(&0), Err(CacheError::CacheMiss));
+            assert!(lru_cache.insert_pinned(0, 0).is_ok());
+            assert!(lru_cache.insert(1, 1).is_ok());
+            assert!(lru_cache.insert(2, 2).is_ok());
+            assert!(lru_cache.is_pinned(&0));
+
+            // 0 is least recently used but pinned
+            assert!(lru_cache.insert(3, 3).is_ok());
+            assert_eq!(lru_cache.query(&0), Ok(&0));
+            assert_eq!(lru_cache.query(&1), Err(CacheError::CacheMiss));
+
+            assert_eq!(lru_cache.pin(&2), Ok(()));
+            assert_eq!(lru_cache.pin(&3), Ok(()));
+            assert_eq!(lru_cache.insert(4, 4), Err(CacheError::AllEntriesPinned));
+            assert!(lru_cache.insert(2, -2).is_ok());
+            assert!(lru_cache.blocks.iter().map(|b| b.block.key).eq([3, 0, 2]));
+
+            assert_eq!(lru_cache.unpin(&3), Ok(()));
+            assert!(lru_cache.insert(4, 4).is_ok());
+            assert_eq!(lru_cache.query(&3), Err(CacheError::CacheMiss));
+
+            // removal drops the pin
+            assert_eq!(lru_cache.remove(&0), Ok(0));
+            assert!(!lru_cache.is_pinned(&0));
+            assert!(lru_cache.insert(5, 5).is_ok());
+            assert!(lru_cache.insert(6, 6).is_ok());
+            assert!(lru_cache.blocks.iter().map(|b| b.block.key).eq([2, 5, 6]));
+
+            let mut mru_cache =
+                LRUCache::<i32, i32>::with_capacity_and_policy(3, EvictionPolicy::MostRecentlyUsed);
+            mru_cache.set_victim_capacity(1);
+            for ele in 0..3 {
+                assert!(mru_cache.insert_pinned(ele, ele).is_ok());
+            }
+            assert_eq!(mru_cache.unpin(&1), Ok(()));
+            assert!(mru_cache.insert(3, 3).is_ok());
+            assert!(mru_cache.victims.iter().map(|b| b.block.key).eq([1]));
+
+            // a victim that cannot be re-admitted stays in the victim buffer
+            assert_eq!(mru_cache.pin(&3), Ok(()));
+            assert_eq!(mru_cache.query(&1), Err(CacheError::AllEntriesPinned));
+            assert!(mru_cache.victims.iter().map(|b| b.block.key).eq([1]));
+        }
+    }
+}
+
+pub mod clock {
+    //! Module providing CLOCK and CLOCK-Pro cache implementations. Unlike the LRUCache,
+    //! a cache hit does not re-arrange any links; it only sets a reference bit on the
+    //! entry. Victims are chosen by a hand sweeping circularly over the entries.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::clock::{ClockCache, ClockProCache};
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! let mut clock_cache = ClockCache::<i32, i32>::with_capacity(2);
+    //!
+    //! assert!(clock_cache.insert(0, 0).is_ok());
+    //! assert!(clock_cache.insert(1, 1).is_ok());
+    //! assert_eq!(clock_cache.query(&0), Ok(&0));
+    //!
+    //! // 0 was referenced, so the hand skips over it and evicts 1
+    //! assert!(clock_cache.insert(2, 2).is_ok());
+    //! assert_eq!(clock_cache.query(&1), Err(CacheError::CacheMiss));
+    //! assert_eq!(clock_cache.query(&0), Ok(&0));
+    //! assert_eq!(clock_cache.remove(&2), Ok(2));
+    //!
+    //! let mut clock_pro_cache = ClockProCache::<i32, i32>::with_capacity(2);
+    //!
+    //! assert!(clock_pro_cache.insert(0, 0).is_ok());
+    //! assert!(clock_pro_cache.insert(1, 1).is_ok());
+    //! assert_eq!(clock_pro_cache.query(&1), Ok(&1));
+    //! assert!(clock_pro_cache.insert(2, 2).is_ok());
+    //!
+    //! assert_eq!(clock_pro_cache.query(&0), Err(CacheError::CacheMiss));
+    //! assert_eq!(clock_pro_cache.query(&1), Ok(&1));
+    //! assert_eq!(clock_pro_cache.query(&2), Ok(&2));
+    //!
+    //! // zero capacity caches are unusable
+    //! let mut clock_cache = ClockCache::<i32, i32>::with_capacity(0);
+    //! assert!(matches!(
+    //!     clock_cache.insert(0, 0),
+    //!     Err(CacheError::CacheBroken(_))
+    //! ));
+    //! ```
+
+    use crate::arena::{Arena, Index};
+    use crate::list::{Link, LinkedList, ListError};
+    use crate::lrucache::CacheError;
+    use std::{collections::HashMap, hash::Hash};
+
+    /// Cache block storing some key and value, along with a reference bit.
+    pub struct ClockBlock<K, V> {
+        pub key: K,
+        pub value: V,
+        pub referenced: bool,
+    }
+
+    /// A CLOCK Cache implemented using a generational arena and a hash map. The hand
+    /// sweeps over the arena slots in order, clearing reference bits until it finds an
+    /// unreferenced entry to evict.
+    pub struct ClockCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        blocks: Arena<ClockBlock<K, V>>,
+        block_refs: HashMap<K, Index>,
+        hand: usize,
+    }
+
+    impl<K, V> ClockCache<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        /// Creates a ClockCache instance with the given capacity. A zero capacity
+        /// ClockCache is unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            ClockCache {
+                blocks: Arena::with_capacity(capacity),
+                block_refs: HashMap::new(),
+                hand: 0,
+            }
+        }
+
+        /// Returns a reference to the value associated with the given key, setting the
+        /// reference bit on its entry. If the key is not present in the cache, we return
+        /// a "cache-miss" error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let index = self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            let block = self
+                .blocks
+                .get_mut(index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            block.referenced = true;
+            Ok(&block.value)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error. Returns the value associated,
+        /// after removal with ownership.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            let index = self.block_refs.remove(key).ok_or(CacheError::CacheMiss)?;
+            let block = self
+                .blocks
+                .remove(&index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            Ok(block.value)
+        }
+
+        /// Inserts a new key value pair into this cache. If this cache is full, the hand
+        /// sweeps forward to find an entry to evict.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            if let Some(index) = self.block_refs.get(&key) {
+                let block = self
+                    .blocks
+                    .get_mut(index)
+                    .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+                block.value = value;
+                block.referenced = true;
+                return Ok(());
+            }
+
+            if self.block_refs.len() == self.blocks.capacity() {
+                self.evict()?;
+            }
+
+            let index = self
+                .blocks
+                .insert(ClockBlock {
+                    key,
+                    value,
+                    referenced: false,
+                })
+                .map_err(|arena_oom| CacheError::CacheBroken(ListError::ListOOM(arena_oom)))?;
+            self.block_refs.insert(key, index);
+
+            Ok(())
+        }
+
+        fn evict(&mut self) -> Result<(), CacheError> {
+            let capacity = self.blocks.capacity();
+            if self.block_refs.is_empty() {
+                return Err(CacheError::CacheBroken(ListError::ListEmpty));
+            }
+
+            loop {
+                let slot = self.hand;
+                self.hand = (self.hand + 1) % capacity;
+
+                let index = match self.blocks.index_at(slot) {
+                    Some(index) => index,
+                    None => continue,
+                };
+
+                let block = self
+                    .blocks
+                    .get_mut(&index)
+                    .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+
+                if block.referenced {
+                    block.referenced = false;
+                    continue;
+                }
+
+                let block = self
+                    .blocks
+                    .remove(&index)
+                    .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+                self.block_refs.remove(&block.key);
+
+                return Ok(());
+            }
+        }
+    }
+
+    /// Classification of a page in a ClockProCache.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum PageStatus {
+        /// Resident page with a small reuse distance.
+        Hot,
+        /// Resident page with a large or unknown reuse distance.
+        Cold,
+        /// Non-resident page, retained only to detect a small reuse distance.
+        Test,
+    }
+
+    #[derive(Clone, Copy)]
+    enum Hand {
+        Hot,
+        Cold,
+        Test,
+    }
+
+    /// Cache page for ClockProCache. Test pages do not hold a value.
+    pub struct ClockProPage<K, V> {
+        pub key: K,
+        pub value: Option<V>,
+        pub status: PageStatus,
+        pub referenced: bool,
+    }
+
+    /// A CLOCK-Pro Cache implemented using a generational arena based linked list,
+    /// traversed circularly, and a hash map. Pages are classified as hot, cold or test,
+    /// with three hands sweeping over the same clock. The number of resident cold pages
+    /// adapts to the workload: a hit on a test page grows it, while a test page expiring
+    /// shrinks it.
+    ///
+    /// This follows "CLOCK-Pro: An Effective Improvement of the CLOCK Replacement" by
+    /// Song Jiang, Feng Chen and Xiaodong Zhang.
+    pub struct ClockProCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        pages: LinkedList<ClockProPage<K, V>>,
+        page_refs: HashMap<K, Link>,
+
+        capacity: usize,
+        cold_capacity: usize,
+
+        hand_hot: Option<Link>,
+        hand_cold: Option<Link>,
+        hand_test: Option<Link>,
+
+        hot_count: usize,
+        cold_count: usize,
+        test_count: usize,
+    }
+
+    impl<K, V> ClockProCache<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        /// Creates a ClockProCache instance with the given capacity for resident pages. Up
+        /// to as many non-resident test pages are tracked. A zero capacity ClockProCache
+        /// is unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            ClockProCache {
+                pages: LinkedList::with_capacity(2 * capacity),
+                page_refs: HashMap::new(),
+                capacity,
+                cold_capacity: capacity,
+                hand_hot: None,
+                hand_cold: None,
+                hand_test: None,
+                hot_count: 0,
+                cold_count: 0,
+                test_count: 0,
+            }
+        }
+
+        /// Returns a reference to the value associated with the given key, setting the
+        /// reference bit on its page. If the key is not resident in the cache, we return a
+        /// "cache-miss" error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let link = self.page_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            let page = self
+                .pages
+                .get_mut_value(link)
+                .map_err(CacheError::CacheBroken)?;
+
+            if page.value.is_some() {
+                page.referenced = true;
+            }
+
+            page.value.as_ref().ok_or(CacheError::CacheMiss)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// resident entry is found, we return a "cache-miss" error. A test page for the
+        /// key is kept, so a re-insert during its test period is still promoted to hot.
+        /// Returns the value associated, after removal with ownership.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            let link = *self.page_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            let status = self
+                .pages
+                .get(&link)
+                .map_err(CacheError::CacheBroken)?
+                .value
+                .status;
+
+            match status {
+                PageStatus::Hot => self.hot_count -= 1,
+                PageStatus::Cold => self.cold_count -= 1,
+                PageStatus::Test => return Err(CacheError::CacheMiss),
+            }
+
+            let page = self.remove_page(&link)?;
+            page.value.ok_or(CacheError::CacheMiss)
+        }
+
+        /// Inserts a new key value pair into this cache. New pages start out cold, while
+        /// pages re-inserted during their test period are promoted to hot. If this cache
+        /// is full, the hands sweep forward to find a cold page to evict.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            if let Some(link) = self.page_refs.get(&key).copied() {
+                let page = self
+                    .pages
+                    .get_mut_value(&link)
+                    .map_err(CacheError::CacheBroken)?;
+
+                if page.value.is_some() {
+                    page.value = Some(value);
+                    page.referenced = true;
+                    return Ok(());
+                }
+
+                if self.cold_capacity < self.capacity {
+                    self.cold_capacity += 1;
+                }
+
+                self.test_count -= 1;
+                self.remove_page(&link)?;
+                self.add_page(ClockProPage {
+                    key,
+                    value: Some(value),
+                    status: PageStatus::Hot,
+                    referenced: false,
+                })?;
+                self.hot_count += 1;
+
+                return Ok(());
+            }
+
+            self.add_page(ClockProPage {
+                key,
+                value: Some(value),
+                status: PageStatus::Cold,
+                referenced: false,
+            })?;
+            self.cold_count += 1;
+
+            Ok(())
+        }
+
+        fn next_link(&self, link: &Link) -> Result<Link, CacheError> {
+            let node = self.pages.get(link).map_err(CacheError::CacheBroken)?;
+            node.next
+                .or_else(|| self.pages.head())
+                .ok_or(CacheError::CacheBroken(ListError::ListEmpty))
+        }
+
+        fn prev_link(&self, link: &Link) -> Result<Link, CacheError> {
+            let node = self.pages.get(link).map_err(CacheError::CacheBroken)?;
+            node.prev
+                .or_else(|| self.pages.tail())
+                .ok_or(CacheError::CacheBroken(ListError::ListEmpty))
+        }
+
+        /// Adds the given page to the clock right behind the hot hand, making room for it
+        /// first.
+        fn add_page(&mut self, page: ClockProPage<K, V>) -> Result<(), CacheError> {
+            self.evict()?;
+
+            let key = page.key;
+            let link = match self.hand_hot {
+                Some(hand_hot) => self.pages.insert_before(&hand_hot, page),
+                None => self.pages.push_back(page),
+            }
+            .map_err(CacheError::CacheBroken)?;
+            self.page_refs.insert(key, link);
+
+            if self.hand_hot.is_none() {
+                self.hand_hot = Some(link);
+                self.hand_test = Some(link);
+            }
+
+            if self.hand_cold == self.hand_hot || self.hand_cold.is_none() {
+                self.hand_cold = Some(link);
+            }
+
+            Ok(())
+        }
+
+        /// Removes the page pointed to by the given link from the clock, moving any hand
+        /// pointing to it back by one.
+        fn remove_page(&mut self, link: &Link) -> Result<ClockProPage<K, V>, CacheError> {
+            let prev = self.prev_link(link)?;
+            let prev = if &prev == link { None } else { Some(prev) };
+
+            for hand in [&mut self.hand_hot, &mut self.hand_cold, &mut self.hand_test] {
+                if hand.as_ref() == Some(link) {
+                    *hand = prev;
+                }
+            }
+
+            let page = self.pages.remove(link).map_err(CacheError::CacheBroken)?;
+            self.page_refs.remove(&page.key);
+
+            Ok(page)
+        }
+
+        /// Moves the hands until there is room for one more resident page. Each step
+        /// moves exactly one hand forward by one page. The hot hand clears the reference
+        /// bits it passes and the test hand drops the test pages it passes, so all three
+        /// targets are met within two sweeps of the clock.
+        fn evict(&mut self) -> Result<(), CacheError> {
+            loop {
+                let hand = if self.capacity < self.test_count {
+                    Hand::Test
+                } else if self.capacity - self.cold_capacity < self.hot_count {
+                    Hand::Hot
+                } else if self.capacity <= self.hot_count + self.cold_count {
+                    Hand::Cold
+                } else {
+                    return Ok(());
+                };
+
+                // a hand never overtakes the next one: hand_hot pushes hand_test ahead,
+                // which in turn pushes hand_cold. A lone page has no test period to end.
+                let hand = match hand {
+                    Hand::Hot if self.hand_hot == self.hand_test && self.pages.len() > 1 => {
+                        Hand::Test
+                    }
+                    hand => hand,
+                };
+                let hand = match hand {
+                    Hand::Test if self.hand_test == self.hand_cold => Hand::Cold,
+                    hand => hand,
+                };
+
+                match hand {
+                    Hand::Hot => self.step_hand_hot()?,
+                    Hand::Cold => self.step_hand_cold()?,
+                    Hand::Test => self.step_hand_test()?,
+                }
+            }
+        }
+
+        fn step_hand_cold(&mut self) -> Result<(), CacheError> {
+            let hand = self
+                .hand_cold
+                .ok_or(CacheError::CacheBroken(ListError::ListEmpty))?;
+            let page = self
+                .pages
+                .get_mut_value(&hand)
+                .map_err(CacheError::CacheBroken)?;
+
+            if page.status == PageStatus::Cold {
+                self.cold_count -= 1;
+
+                if page.referenced {
+                    page.status = PageStatus::Hot;
+                    page.referenced = false;
+                    self.hot_count += 1;
+                } else {
+                    page.status = PageStatus::Test;
+                    page.value = None;
+                    self.test_count += 1;
+                }
+            }
+
+            self.hand_cold = Some(self.next_link(&hand)?);
+
+            Ok(())
+        }
+
+        fn step_hand_hot(&mut self) -> Result<(), CacheError> {
+            let hand = self
+                .hand_hot
+                .ok_or(CacheError::CacheBroken(ListError::ListEmpty))?;
+            let page = self
+                .pages
+                .get_mut_value(&hand)
+                .map_err(CacheError::CacheBroken)?;
+
+            if page.status == PageStatus::Hot {
+                if page.referenced {
+                    page.referenced = false;
+                } else {
+                    page.status = PageStatus::Cold;
+                    self.hot_count -= 1;
+                    self.cold_count += 1;
+                }
+            }
+
+            self.hand_hot = Some(self.next_link(&hand)?);
+
+            Ok(())
+        }
+
+        fn step_hand_test(&mut self) -> Result<(), CacheError> {
+            let hand = self
+                .hand_test
+                .ok_or(CacheError::CacheBroken(ListError::ListEmpty))?;
+            let page = self.pages.get(&hand).map_err(CacheError::CacheBroken)?;
+
+            if page.value.status == PageStatus::Test {
+                self.remove_page(&hand)?;
+                self.test_count -= 1;
+
+                if self.cold_capacity > 1 {
+                    self.cold_capacity -= 1;
+                }
+            }
+
+            if let Some(hand) = self.hand_test {
+                self.hand_test = Some(self.next_link(&hand)?);
+            }
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn clock_cache_consistency() {
+            let mut clock_cache = ClockCache::<i32, i32>::with_capacity(0);
+            assert_eq!(
+                clock_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+
+            let mut clock_cache = ClockCache::<i32, i32>::with_capacity(3);
+            for ele in 0..3 {
+                assert!(clock_cache.insert(ele, ele).is_ok());
+            }
+
+            assert_eq!(clock_cache.query(&0), Ok(&0));
+            assert_eq!(clock_cache.query(&2), Ok(&2));
+
+            // hand clears the bit on 0, evicts 1 and stops right after it
+            assert!(clock_cache.insert(3, 3).is_ok());
+            assert_eq!(clock_cache.hand, 2);
+            assert_eq!(clock_cache.query(&1), Err(CacheError::CacheMiss));
+
+            // hand clears the bit on 2, finds 0 unreferenced
+            assert!(clock_cache.insert(4, 4).is_ok());
+            assert_eq!(clock_cache.query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(clock_cache.query(&2), Ok(&2));
+
+            assert!(clock_cache.insert(2, -2).is_ok());
+            assert_eq!(clock_cache.query(&2), Ok(&-2));
+
+            assert_eq!(clock_cache.remove(&3), Ok(3));
+            assert_eq!(clock_cache.remove(&3), Err(CacheError::CacheMiss));
+            assert!(clock_cache.insert(5, 5).is_ok());
+            assert_eq!(clock_cache.block_refs.len(), 3);
+        }
+
+        #[test]
+        fn clock_pro_cache_consistency() {
+            let mut clock_pro_cache = ClockProCache::<i32, i32>::with_capacity(0);
+            assert!(matches!(
+                clock_pro_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(_))
+            ));
+
+            let capacity = 4;
+            let mut clock_pro_cache = ClockProCache::<i32, i32>::with_capacity(capacity);
+
+            for ele in 0..capacity as i32 {
+                assert!(clock_pro_cache.insert(ele, ele).is_ok());
+            }
+
+            // the evicted page is retained as a test page
+            assert!(clock_pro_cache.insert(4, 4).is_ok());
+            let evicted: Vec<i32> = (0..capacity as i32)
+                .filter(|x| clock_pro_cache.query(x).is_err())
+                .collect();
+            assert_eq!(evicted.len(), 1);
+            assert_eq!(clock_pro_cache.test_count, 1);
+
+            let x = evicted[0];
+            let link = clock_pro_cache.page_refs[&x];
+            let page = clock_pro_cache.pages.get_mut_value(&link).unwrap();
+            assert_eq!(page.status, PageStatus::Test);
+
+            // re-inserting it during its test period makes it hot
+            assert!(clock_pro_cache.insert(x, x).is_ok());
+            let link = clock_pro_cache.page_refs[&x];
+            let page = clock_pro_cache.pages.get_mut_value(&link).unwrap();
+            assert_eq!(page.status, PageStatus::Hot);
+            assert_eq!(clock_pro_cache.query(&x), Ok(&x));
+
+            for ele in 0..(8 * capacity) as i32 {
+                assert!(clock_pro_cache.insert(ele, ele).is_ok());
+                assert_eq!(clock_pro_cache.query(&ele), Ok(&ele));
+
+                assert!(clock_pro_cache.hot_count + clock_pro_cache.cold_count <= capacity);
+                assert!(clock_pro_cache.test_count <= capacity);
+                assert_eq!(
+                    clock_pro_cache.pages.len(),
+                    clock_pro_cache.hot_count
+                        + clock_pro_cache.cold_count
+                        + clock_pro_cache.test_count
+                );
+            }
+
+            let resident = (0..(8 * capacity) as i32)
+                .filter(|x| clock_pro_cache.query(x).is_ok())
+                .count();
+            assert_eq!(resident, capacity);
+
+            let ele = (0..(8 * capacity) as i32)
+                .find(|x| clock_pro_cache.query(x).is_ok())
+                .unwrap();
+            assert_eq!(clock_pro_cache.remove(&ele), Ok(ele));
+            assert_eq!(clock_pro_cache.query(&ele), Err(CacheError::CacheMiss));
+            assert_eq!(clock_pro_cache.remove(&ele), Err(CacheError::CacheMiss));
+        }
+
+        #[test]
+        fn clock_pro_cache_cold_pages_and_test_pages() {
+            let capacity = 3;
+            let mut clock_pro_cache = ClockProCache::<i32, i32>::with_capacity(capacity);
+
+            // never referenced pages stay cold and leave a test page behind on eviction
+            for ele in 0..(4 * capacity) as i32 {
+                assert!(clock_pro_cache.insert(ele, ele).is_ok());
+
+                assert_eq!(clock_pro_cache.hot_count, 0);
+                assert!(clock_pro_cache.cold_count <= capacity);
+                assert!(clock_pro_cache.test_count <= capacity);
+                assert_eq!(
+                    clock_pro_cache.pages.len(),
+                    clock_pro_cache.cold_count + clock_pro_cache.test_count
+                );
+            }
+            assert_eq!(clock_pro_cache.cold_count, capacity);
+            assert_eq!(clock_pro_cache.test_count, capacity);
+
+            let ghost = (0..(4 * capacity) as i32)
+                .find(|x| {
+                    clock_pro_cache.page_refs.contains_key(x) && clock_pro_cache.query(x).is_err()
+                })
+                .unwrap();
+
+            // removing a key with only a test page keeps the test page
+            assert_eq!(clock_pro_cache.remove(&ghost), Err(CacheError::CacheMiss));
+            assert_eq!(clock_pro_cache.test_count, capacity);
+            let link = clock_pro_cache.page_refs[&ghost];
+            let page = clock_pro_cache.pages.get_mut_value(&link).unwrap();
+            assert_eq!(page.status, PageStatus::Test);
+
+            assert!(clock_pro_cache.insert(ghost, ghost).is_ok());
+            let link = clock_pro_cache.page_refs[&ghost];
+            let page = clock_pro_cache.pages.get_mut_value(&link).unwrap();
+            assert_eq!(page.status, PageStatus::Hot);
+            assert_eq!(
+                clock_pro_cache.hot_count + clock_pro_cache.cold_count,
+                capacity
+            );
+        }
+    }
+}
+
+pub mod sieve {
+    //! Module providing a SIEVE Cache implementation.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::sieve::SieveCache;
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! let mut sieve_cache = SieveCache::<i32, i32>::with_capacity(3);
+    //!
+    //! for ele in 0..3 {
+    //!     assert!(sieve_cache.insert(ele, ele).is_ok());
+    //! }
+    //!
+    //! assert_eq!(sieve_cache.query(&0), Ok(&0));
+    //!
+    //! // 0 was visited, so the hand skips over it and evicts 1
+    //! assert!(sieve_cache.insert(3, 3).is_ok());
+    //! assert_eq!(sieve_cache.query(&1), Err(CacheError::CacheMiss));
+    //! assert_eq!(sieve_cache.query(&0), Ok(&0));
+    //!
+    //! assert_eq!(sieve_cache.remove(&3), Ok(3));
+    //! assert_eq!(sieve_cache.remove(&3), Err(CacheError::CacheMiss));
+    //!
+    //! // zero capacity SieveCache is unusable
+    //! let mut sieve_cache = SieveCache::<i32, i32>::with_capacity(0);
+    //!
+    //! assert!(matches!(
+    //!     sieve_cache.insert(0, 0),
+    //!     Err(CacheError::CacheBroken(_))
+    //! ));
+    //! ```
+
+    use crate::list::{Link, LinkedList, ListError};
+    use crate::lrucache::CacheError;
+    use std::{collections::HashMap, hash::Hash};
+
+    /// Cache block storing some key and value, along with a visited flag.
+    pub struct SieveBlock<K, V> {
+        pub key: K,
+        pub value: V,
+        pub visited: bool,
+    }
+
+    /// A SIEVE Cache implemented using a generational arena based linked list and a
+    /// hash map. New entries are inserted at the head, and a cache hit only sets the
+    /// visited flag on the entry. The hand moves from the tail towards the head,
+    /// clearing visited flags, and evicts the first entry not visited.
+    ///
+    /// This follows "SIEVE is Simpler than LRU: an Efficient Turn-Key Eviction Algorithm
+    /// for Web Caches" by Yazhuo Zhang et al. (NSDI '24).
+    pub struct SieveCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        blocks: LinkedList<SieveBlock<K, V>>,
+        block_refs: HashMap<K, Link>,
+        hand: Option<Link>,
+    }
+
+    impl<K, V> SieveCache<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        /// Creates a SieveCache instance with the given capacity. A zero capacity
+        /// SieveCache is unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            SieveCache {
+                blocks: LinkedList::with_capacity(capacity),
+                block_refs: HashMap::new(),
+                hand: None,
+            }
+        }
+
+        /// Returns a reference to the value associated with the given key, marking its
+        /// entry as visited. If the key is not present in the cache, we return a
+        /// "cache-miss" error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let link = self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            let block = self
+                .blocks
+                .get_mut_value(link)
+                .map_err(CacheError::CacheBroken)?;
+            block.visited = true;
+            Ok(&block.value)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error. Returns the value associated,
+        /// after removal with ownership.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            let link = self.block_refs.remove(key).ok_or(CacheError::CacheMiss)?;
+
+            let mut cursor = self.blocks.cursor_mut(Some(link));
+            let block = cursor
+                .remove_current_and_move_prev()
+                .map_err(CacheError::CacheBroken)?;
+
+            if self.hand == Some(link) {
+                self.hand = cursor.current();
+            }
+
+            Ok(block.value)
+        }
+
+        /// Inserts a new key value pair at the head of this cache. If this cache is full,
+        /// the hand sweeps towards the head to find an entry to evict.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            if let Some(link) = self.block_refs.get(&key) {
+                let block = self
+                    .blocks
+                    .get_mut_value(link)
+                    .map_err(CacheError::CacheBroken)?;
+                block.value = value;
+                block.visited = true;
+                return Ok(());
+            }
+
+            if self.blocks.is_full() {
+                self.evict()?;
+            }
+
+            let link = self
+                .blocks
+                .push_front(SieveBlock {
+                    key,
+                    value,
+                    visited: false,
+                })
+                .map_err(CacheError::CacheBroken)?;
+            self.block_refs.insert(key, link);
+
+            Ok(())
+        }
+
+        fn evict(&mut self) -> Result<(), CacheError> {
+            if self.blocks.is_empty() {
+                return Err(CacheError::CacheBroken(ListError::ListEmpty));
+            }
+
+            let mut cursor = self.blocks.cursor_mut(self.hand);
+
+            loop {
+                match cursor.current_mut() {
+                    Some(block) if block.visited => {
+                        block.visited = false;
+                        cursor.move_prev();
+                    }
+                    Some(_) => break,
+                    None => cursor.move_prev(),
+                }
+            }
+
+            let block = cursor
+                .remove_current_and_move_prev()
+                .map_err(CacheError::CacheBroken)?;
+            self.hand = cursor.current();
+            self.block_refs.remove(&block.key);
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn sieve_cache_consistency() {
+            let mut sieve_cache = SieveCache::<i32, i32>::with_capacity(0);
+            assert_eq!(
+                sieve_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+
+            let mut sieve_cache = SieveCache::<i32, i32>::with_capacity(4);
+            for ele in 0..4 {
+                assert!(sieve_cache.insert(ele, ele).is_ok());
+            }
+
+            assert_eq!(sieve_cache.query(&0), Ok(&0));
+            assert_eq!(sieve_cache.query(&1), Ok(&1));
+            assert_eq!(sieve_cache.query(&3), Ok(&3));
+
+            // hand clears 0 and 1, evicts 2 and stays right before it
+            assert!(sieve_cache.insert(4, 4).is_ok());
+            assert_eq!(sieve_cache.query(&2), Err(CacheError::CacheMiss));
+            assert_eq!(sieve_cache.hand, Some(sieve_cache.block_refs[&3]));
+
+            // hand clears 3, evicts 4 at the head and moves past it
+            assert!(sieve_cache.insert(5, 5).is_ok());
+            assert_eq!(sieve_cache.query(&4), Err(CacheError::CacheMiss));
+            assert_eq!(sieve_cache.hand, None);
+
+            // hand wraps around to the tail and evicts 0
+            assert!(sieve_cache.insert(6, 6).is_ok());
+            assert_eq!(sieve_cache.query(&0), Err(CacheError::CacheMiss));
+            assert!(sieve_cache.blocks.iter().map(|b| b.key).eq([6, 5, 3, 1]));
+            assert_eq!(sieve_cache.hand, Some(sieve_cache.block_refs[&1]));
+
+            // removing the entry under the hand moves the hand along
+            assert_eq!(sieve_cache.remove(&1), Ok(1));
+            assert_eq!(sieve_cache.hand, Some(sieve_cache.block_refs[&3]));
+            assert_eq!(sieve_cache.remove(&1), Err(CacheError::CacheMiss));
+
+            assert!(sieve_cache.insert(3, -3).is_ok());
+            assert_eq!(sieve_cache.query(&3), Ok(&-3));
+            assert!(sieve_cache.insert(7, 7).is_ok());
+            assert!(sieve_cache.blocks.is_full());
+        }
+
+        #[test]
+        fn sieve_cache_zero_capacity() {
+            let mut sieve_cache = SieveCache::<i32, i32>::with_capacity(0);
+            assert_eq!(sieve_cache.query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(sieve_cache.remove(&0), Err(CacheError::CacheMiss));
+            assert_eq!(
+                sieve_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+            assert!(sieve_cache.blocks.is_empty());
+            assert_eq!(sieve_cache.hand, None);
+        }
+
+        #[test]
+        fn sieve_cache_repeated_keys() {
+            let mut sieve_cache = SieveCache::<i32, i32>::with_capacity(2);
+            for ele in 0..3 {
+                assert!(sieve_cache.insert(0, ele).is_ok());
+            }
+            assert_eq!(sieve_cache.blocks.len(), 1);
+            assert_eq!(sieve_cache.query(&0), Ok(&2));
+
+            // a repeated insert marks the entry visited, so the hand passes it over
+            assert!(sieve_cache.insert(1, 1).is_ok());
+            assert!(sieve_cache.insert(0, 3).is_ok());
+            assert!(sieve_cache.insert(2, 2).is_ok());
+            assert_eq!(sieve_cache.query(&1), Err(CacheError::CacheMiss));
+            assert_eq!(sieve_cache.query(&0), Ok(&3));
+
+            // with every entry visited, the hand clears them all and evicts the oldest
+            assert_eq!(sieve_cache.query(&2), Ok(&2));
+            assert!(sieve_cache.insert(4, 4).is_ok());
+            assert_eq!(sieve_cache.query(&0), Err(CacheError::CacheMiss));
+            assert!(sieve_cache.blocks.iter().map(|b| b.key).eq([4, 2]));
+        }
+    }
+}
+
+pub mod s3fifo {
+    //! Module providing an S3-FIFO Cache implementation.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::s3fifo::S3FifoCache;
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! let capacity = 10;
+    //! let mut s3fifo_cache = S3FifoCache::<i32, i32>::with_capacity(capacity);
+    //!
+    //! assert!(s3fifo_cache.insert(0, 0).is_ok());
+    //! assert_eq!(s3fifo_cache.query(&0), Ok(&0));
+    //!
+    //! // a scan of one hit wonders does not flush out 0, which was accessed again
+    //! for ele in 1..100 {
+    //!     assert!(s3fifo_cache.insert(ele, ele).is_ok());
+    //! }
+    //!
+    //! assert_eq!(s3fifo_cache.query(&0), Ok(&0));
+    //! assert_eq!(s3fifo_cache.query(&1), Err(CacheError::CacheMiss));
+    //!
+    //! assert_eq!(s3fifo_cache.remove(&0), Ok(0));
+    //! assert_eq!(s3fifo_cache.remove(&0), Err(CacheError::CacheMiss));
+    //!
+    //! // zero capacity S3FifoCache is unusable
+    //! let mut s3fifo_cache = S3FifoCache::<i32, i32>::with_capacity(0);
+    //!
+    //! assert!(matches!(
+    //!     s3fifo_cache.insert(0, 0),
+    //!     Err(CacheError::CacheBroken(_))
+    //! ));
+    //! ```
+
+    use crate::list::{Link, LinkedList, ListError};
+    use crate::lrucache::CacheError;
+    use std::{collections::HashMap, hash::Hash};
+
+    /// Saturation value for the 2-bit access counters.
+    const MAX_FREQ: u8 = 3;
+
+    /// Cache block storing some key and value, along with a 2-bit access counter.
+    pub struct S3FifoBlock<K, V> {
+        pub key: K,
+        pub value: V,
+        pub freq: u8,
+    }
+
+    /// Refers to the position of a key in one of the queues.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum QueueRef {
+        Small(Link),
+        Main(Link),
+        Ghost(Link),
+    }
+
+    /// An S3-FIFO Cache implemented using three generational arena based linked lists
+    /// as FIFO queues and a hash map. New entries are admitted to a small queue taking up
+    /// 10% of the capacity, and are moved to the main queue only if accessed again before
+    /// reaching its end. Keys evicted from the small queue are remembered in a ghost
+    /// queue, so that they are admitted straight to the main queue if they come back.
+    ///
+    /// This follows "FIFO queues are all you need for cache eviction" by Juncheng Yang et
+    /// al. (SOSP '23).
+    pub struct S3FifoCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        small: LinkedList<S3FifoBlock<K, V>>,
+        main: LinkedList<S3FifoBlock<K, V>>,
+        ghost: LinkedList<K>,
+        block_refs: HashMap<K, QueueRef>,
+
+        capacity: usize,
+        main_capacity: usize,
+    }
+
+    impl<K, V> S3FifoCache<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        /// Creates an S3FifoCache instance with the given capacity. The ghost queue
+        /// remembers as many keys as the main queue can hold. A zero capacity
+        /// S3FifoCache is unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            let main_capacity = capacity - capacity / 10;
+
+            S3FifoCache {
+                small: LinkedList::with_capacity(capacity),
+                main: LinkedList::with_capacity(capacity),
+                ghost: LinkedList::with_capacity(main_capacity),
+                block_refs: HashMap::new(),
+                capacity,
+                main_capacity,
+            }
+        }
+
+        fn block_mut(&mut self, key: &K) -> Result<&mut S3FifoBlock<K, V>, CacheError> {
+            match self.block_refs.get(key) {
+                Some(QueueRef::Small(link)) => self.small.get_mut_value(link),
+                Some(QueueRef::Main(link)) => self.main.get_mut_value(link),
+                _ => return Err(CacheError::CacheMiss),
+            }
+            .map_err(CacheError::CacheBroken)
+        }
+
+        /// Returns a reference to the value associated with the given key, incrementing
+        /// its access counter. If the key is not present in the cache, we return a
+        /// "cache-miss" error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let block = self.block_mut(key)?;
+            block.freq = MAX_FREQ.min(block.freq + 1);
+            Ok(&block.value)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error. Keys in the ghost queue stay
+        /// there, just like non-resident entries in [`LirsCache`]. Returns the value
+        /// associated, after removal with ownership.
+        ///
+        /// [`LirsCache`]: crate::lirs::LirsCache
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            let block = match self.block_refs.get(key).copied() {
+                Some(QueueRef::Small(link)) => self.small.remove(&link),
+                Some(QueueRef::Main(link)) => self.main.remove(&link),
+                Some(QueueRef::Ghost(_)) | None => return Err(CacheError::CacheMiss),
+            }
+            .map_err(CacheError::CacheBroken)?;
+            self.block_refs.remove(key);
+
+            Ok(block.value)
+        }
+
+        /// Inserts a new key value pair into this cache. Keys remembered in the ghost
+        /// queue are admitted to the main queue, while others are admitted to the small
+        /// queue. If this cache is full, entries are evicted first.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            if let Ok(block) = self.block_mut(&key) {
+                block.value = value;
+                block.freq = MAX_FREQ.min(block.freq + 1);
+                return Ok(());
+            }
+
+            // look the key up in the ghost queue before evicting, which may push it out
+            let ghost = match self.block_refs.get(&key) {
+                Some(QueueRef::Ghost(link)) => Some(*link),
+                _ => None,
+            };
+            if let Some(link) = ghost {
+                self.ghost.remove(&link).map_err(CacheError::CacheBroken)?;
+                self.block_refs.remove(&key);
+            }
+
+            while self.small.len() + self.main.len() >= self.capacity {
+                self.evict()?;
+            }
+
+            let block = S3FifoBlock {
+                key,
+                value,
+                freq: 0,
+            };
+
+            let queue_ref = if ghost.is_some() {
+                let link = self
+                    .main
+                    .push_back(block)
+                    .map_err(CacheError::CacheBroken)?;
+                QueueRef::Main(link)
+            } else {
+                let link = self
+                    .small
+                    .push_back(block)
+                    .map_err(CacheError::CacheBroken)?;
+                QueueRef::Small(link)
+            };
+            self.block_refs.insert(key, queue_ref);
+
+            Ok(())
+        }
+
+        fn evict(&mut self) -> Result<(), CacheError> {
+            if !self.small.is_empty()
+                && (self.small.len() * 10 >= self.capacity || self.main.is_empty())
+            {
+                self.evict_small()
+            } else {
+                self.evict_main()
+            }
+        }
+
+        /// Evicts from the small queue into the ghost queue, moving entries that were
+        /// accessed while in the small queue over to the main queue instead.
+        fn evict_small(&mut self) -> Result<(), CacheError> {
+            while !self.small.is_empty() {
+                let block = self.small.pop_front().map_err(CacheError::CacheBroken)?;
+                let key = block.key;
+
+                if block.freq == 0 {
+                    if self.ghost.is_full() {
+                        let ghost_key = self.ghost.pop_front().map_err(CacheError::CacheBroken)?;
+                        self.block_refs.remove(&ghost_key);
+                    }
+
+                    let link = self.ghost.push_back(key).map_err(CacheError::CacheBroken)?;
+                    self.block_refs.insert(key, QueueRef::Ghost(link));
+
+                    return Ok(());
+                }
+
+                if self.main.len() >= self.main_capacity {
+                    self.evict_main()?;
+                }
+
+                let link = self
+                    .main
+                    .push_back(block)
+                    .map_err(CacheError::CacheBroken)?;
+                self.block_refs.insert(key, QueueRef::Main(link));
+            }
+
+            Ok(())
+        }
+
+        /// Evicts from the main queue, re-inserting entries accessed while in the main
+        /// queue with their access counter decremented.
+        fn evict_main(&mut self) -> Result<(), CacheError> {
+            loop {
+                let link = self
+                    .main
+                    .head()
+                    .ok_or(CacheError::CacheBroken(ListError::ListEmpty))?;
+                let block = self
+                    .main
+                    .get_mut_value(&link)
+                    .map_err(CacheError::CacheBroken)?;
+
+                if block.freq == 0 {
+                    break;
+                }
+
+                block.freq -= 1;
+                self.main
+                    .reposition_to_tail(&link)
+                    .map_err(CacheError::CacheBroken)?;
+            }
+
+            let block = self.main.pop_front().map_err(CacheError::CacheBroken)?;
+            self.block_refs.remove(&block.key);
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn s3fifo_cache_consistency() {
+            let mut s3fifo_cache = S3FifoCache::<i32, i32>::with_capacity(0);
+            assert_eq!(
+                s3fifo_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+
+            let capacity = 10;
+            let mut s3fifo_cache = S3FifoCache::<i32, i32>::with_capacity(capacity);
+            for ele in 0..capacity as i32 {
+                assert!(s3fifo_cache.insert(ele, ele).is_ok());
+            }
+            assert_eq!(s3fifo_cache.small.len(), capacity);
+
+            // 0 is evicted to the ghost queue
+            assert!(s3fifo_cache.insert(10, 10).is_ok());
+            assert_eq!(s3fifo_cache.query(&0), Err(CacheError::CacheMiss));
+            assert!(s3fifo_cache.ghost.iter().eq([0].iter()));
+
+            // 1 was accessed, so it moves to the main queue and 2 is evicted instead
+            assert_eq!(s3fifo_cache.query(&1), Ok(&1));
+            assert!(s3fifo_cache.insert(11, 11).is_ok());
+            assert_eq!(s3fifo_cache.query(&2), Err(CacheError::CacheMiss));
+            assert!(s3fifo_cache.ghost.iter().eq([0, 2].iter()));
+
+            // 0 comes back from the ghost queue straight into the main queue
+            assert!(s3fifo_cache.insert(0, 0).is_ok());
+            assert!(s3fifo_cache.ghost.iter().eq([2, 3].iter()));
+            assert!(s3fifo_cache.main.iter().map(|b| b.key).eq([1, 0]));
+
+            // the main queue survives a scan
+            for ele in 100..200 {
+                assert!(s3fifo_cache.insert(ele, ele).is_ok());
+            }
+            assert_eq!(s3fifo_cache.query(&0), Ok(&0));
+            assert_eq!(s3fifo_cache.query(&1), Ok(&1));
+            assert_eq!(s3fifo_cache.ghost.len(), s3fifo_cache.main_capacity);
+            assert_eq!(
+                s3fifo_cache.block_refs.len(),
+                capacity + s3fifo_cache.main_capacity
+            );
+
+            // access counters saturate at 3, and are spent on re-insertion in the main queue
+            for _ in 0..5 {
+                assert_eq!(s3fifo_cache.query(&0), Ok(&0));
+            }
+            let block = s3fifo_cache.block_mut(&0).unwrap();
+            assert_eq!(block.freq, MAX_FREQ);
+
+            // 1 goes around twice with its counter at 2, and 0 with its counter at 3
+            s3fifo_cache.evict_main().unwrap();
+            assert_eq!(s3fifo_cache.query(&1), Err(CacheError::CacheMiss));
+            assert_eq!(s3fifo_cache.block_mut(&0).unwrap().freq, MAX_FREQ - 2);
+
+            assert_eq!(s3fifo_cache.remove(&0), Ok(0));
+            assert_eq!(s3fifo_cache.remove(&0), Err(CacheError::CacheMiss));
+            assert_eq!(s3fifo_cache.remove(&199), Ok(199));
+            assert_eq!(s3fifo_cache.remove(&100), Err(CacheError::CacheMiss));
+        }
+
+        #[test]
+        fn s3fifo_cache_ghost_readmission() {
+            let capacity = 10;
+            let mut s3fifo_cache = S3FifoCache::<i32, i32>::with_capacity(capacity);
+            for ele in 0..capacity as i32 + 9 {
+                assert!(s3fifo_cache.insert(ele, ele).is_ok());
+            }
+            assert_eq!(s3fifo_cache.ghost.len(), s3fifo_cache.main_capacity);
+            assert_eq!(s3fifo_cache.ghost.peek_front(), Ok(&0));
+
+            // making room pushes 0 out of the full ghost queue, yet it is re-admitted
+            assert!(s3fifo_cache.insert(0, -1).is_ok());
+            assert!(s3fifo_cache.main.iter().map(|b| b.key).eq([0]));
+            assert!(!s3fifo_cache.ghost.iter().any(|key| *key == 0));
+            assert_eq!(s3fifo_cache.query(&0), Ok(&-1));
+        }
+
+        #[test]
+        fn s3fifo_cache_zero_capacity() {
+            let mut s3fifo_cache = S3FifoCache::<i32, i32>::with_capacity(0);
+            assert_eq!(s3fifo_cache.query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(s3fifo_cache.remove(&0), Err(CacheError::CacheMiss));
+            assert_eq!(
+                s3fifo_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+            assert!(s3fifo_cache.block_refs.is_empty());
+
+            // a single entry cycles through the ghost queue
+            let mut s3fifo_cache = S3FifoCache::<i32, i32>::with_capacity(1);
+            assert!(s3fifo_cache.insert(0, 0).is_ok());
+            assert!(s3fifo_cache.insert(1, 1).is_ok());
+            assert!(s3fifo_cache.ghost.iter().eq([0].iter()));
+            assert!(s3fifo_cache.insert(0, 0).is_ok());
+            assert!(s3fifo_cache.main.iter().map(|b| b.key).eq([0]));
+            assert!(s3fifo_cache.ghost.iter().eq([1].iter()));
+        }
+
+        #[test]
+        fn s3fifo_cache_repeated_keys() {
+            let mut s3fifo_cache = S3FifoCache::<i32, i32>::with_capacity(2);
+            for ele in 0..3 {
+                assert!(s3fifo_cache.insert(0, ele).is_ok());
+            }
+            assert_eq!(s3fifo_cache.small.len(), 1);
+            assert_eq!(s3fifo_cache.block_mut(&0).unwrap().freq, 2);
+            assert_eq!(s3fifo_cache.query(&0), Ok(&2));
+
+            // repeated inserts count as accesses, so 0 moves to the main queue
+            assert!(s3fifo_cache.insert(1, 1).is_ok());
+            assert!(s3fifo_cache.insert(2, 2).is_ok());
+            assert!(s3fifo_cache.main.iter().map(|b| b.key).eq([0]));
+            assert!(s3fifo_cache.ghost.iter().eq([1].iter()));
+
+            // removing a key in the ghost queue misses, but keeps its history
+            assert_eq!(s3fifo_cache.remove(&1), Err(CacheError::CacheMiss));
+            assert!(s3fifo_cache.ghost.iter().eq([1].iter()));
+            assert!(s3fifo_cache.insert(1, -1).is_ok());
+            assert!(s3fifo_cache.main.iter().map(|b| b.key).eq([0, 1]));
+        }
+    }
+}
+
+pub mod lirs {
+    //! Module providing a Low Inter-reference Recency Set (LIRS) Cache implementation.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::lirs::LirsCache;
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! // 2 entries for LIR blocks, 1 entry for resident HIR blocks
+    //! let mut lirs_cache = LirsCache::<i32, i32>::with_capacities(2, 1);
+    //!
+    //! let mut hits = 0;
+    //! for _ in 0..10 {
+    //!     // looping over more keys than the capacity
+    //!     for ele in 0..4 {
+    //!         match lirs_cache.query(&ele) {
+    //!             Ok(_) => hits += 1,
+    //!             Err(CacheError::CacheMiss) => lirs_cache.insert(ele, ele).unwrap(),
+    //!             Err(err) => panic!("{}", err),
+    //!         }
+    //!     }
+    //! }
+    //!
+    //! // an LRUCache would miss every time
+    //! assert!(hits >= 2 * 9);
+    //!
+    //! assert_eq!(lirs_cache.remove(&0), Ok(0));
+    //! assert_eq!(lirs_cache.remove(&0), Err(CacheError::CacheMiss));
+    //!
+    //! // zero capacity LirsCache is unusable
+    //! let mut lirs_cache = LirsCache::<i32, i32>::with_capacity(0);
+    //!
+    //! assert!(matches!(
+    //!     lirs_cache.insert(0, 0),
+    //!     Err(CacheError::CacheBroken(_))
+    //! ));
+    //! ```
+
+    use crate::list::{Link, LinkedList, ListError};
+    use crate::lrucache::CacheError;
+    use std::{collections::HashMap, hash::Hash};
+
+    /// Status of an entry in a LirsCache.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum LirsStatus {
+        /// Resident entry with a low inter-reference recency.
+        Lir,
+        /// Resident entry with a high inter-reference recency.
+        ResidentHir,
+        /// Non-resident entry, retained in the stack to measure its recency.
+        NonResidentHir,
+    }
+
+    /// Entry in a LirsCache. Non-resident entries do not hold a value. The queue link
+    /// points into the resident HIR queue for resident HIR entries, and into the
+    /// non-resident queue for non-resident entries.
+    struct LirsEntry<V> {
+        value: Option<V>,
+        status: LirsStatus,
+        stack_link: Option<Link>,
+        queue_link: Option<Link>,
+    }
+
+    /// A Low Inter-reference Recency Set (LIRS) Cache implemented using generational arena
+    /// based linked lists and a hash map. The LIRS stack S orders entries by recency,
+    /// with the bottom always being an LIR entry. The queue Q holds resident HIR entries
+    /// in eviction order. Evicted HIR entries still in S are kept as non-resident entries,
+    /// so that a re-reference with a small recency promotes them to LIR.
+    ///
+    /// Non-resident entries are bounded by the capacity of this cache: when exceeded, the
+    /// oldest non-resident entry is dropped from the stack.
+    ///
+    /// This follows "LIRS: An Efficient Low Inter-reference Recency Set Replacement
+    /// Policy to Improve Buffer Cache Performance" by Song Jiang and Xiaodong Zhang.
+    pub struct LirsCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        entries: HashMap<K, LirsEntry<V>>,
+
+        stack: LinkedList<K>,
+        queue: LinkedList<K>,
+        non_resident: LinkedList<K>,
+
+        lir_capacity: usize,
+        hir_capacity: usize,
+        lir_count: usize,
+    }
+
+    impl<K, V> LirsCache<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        /// Creates a LirsCache instance with the given capacity, 1% of which (at least
+        /// one entry) is set aside for resident HIR entries. A zero capacity LirsCache is
+        /// unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            let hir_capacity = (capacity / 100).max(1).min(capacity);
+            Self::with_capacities(capacity - hir_capacity, hir_capacity)
+        }
+
+        /// Creates a LirsCache instance with the given capacities for LIR entries and
+        /// resident HIR entries.
+        pub fn with_capacities(lir_capacity: usize, hir_capacity: usize) -> Self {
+            let capacity = lir_capacity + hir_capacity;
+
+            LirsCache {
+                entries: HashMap::new(),
+                stack: LinkedList::with_capacity(2 * capacity),
+                queue: LinkedList::with_capacity(capacity),
+                non_resident: LinkedList::with_capacity(capacity),
+                lir_capacity,
+                hir_capacity,
+                lir_count: 0,
+            }
+        }
+
+        fn capacity(&self) -> usize {
+            self.lir_capacity + self.hir_capacity
+        }
+
+        fn resident_len(&self) -> usize {
+            self.lir_count + self.queue.len()
+        }
+
+        fn entry_mut(&mut self, key: &K) -> Result<&mut LirsEntry<V>, CacheError> {
+            self.entries
+                .get_mut(key)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))
+        }
+
+        /// Returns a reference to the value associated with the given key. If the key is
+        /// not resident in the cache, we return a "cache-miss" error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            match self.entries.get(key) {
+                Some(entry) if entry.value.is_some() => self.hit(key)?,
+                _ => return Err(CacheError::CacheMiss),
+            }
+
+            self.entries
+                .get(key)
+                .and_then(|entry| entry.value.as_ref())
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// resident entry is found, we return a "cache-miss" error. Non-resident entries
+        /// keep their history, just like test pages in [`ClockProCache`]. Returns the value
+        /// associated, after removal with ownership.
+        ///
+        /// [`ClockProCache`]: crate::clock::ClockProCache
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            match self.entries.get(key) {
+                Some(entry) if entry.status != LirsStatus::NonResidentHir => {}
+                _ => return Err(CacheError::CacheMiss),
+            }
+
+            let entry = self.entries.remove(key).ok_or(CacheError::CacheMiss)?;
+
+            if let Some(link) = entry.stack_link {
+                self.stack.remove(&link).map_err(CacheError::CacheBroken)?;
+            }
+
+            if let Some(link) = entry.queue_link {
+                match entry.status {
+                    LirsStatus::NonResidentHir => self.non_resident.remove(&link),
+                    _ => self.queue.remove(&link),
+                }
+                .map_err(CacheError::CacheBroken)?;
+            }
+
+            if entry.status == LirsStatus::Lir {
+                self.lir_count -= 1;
+                self.prune()?;
+            }
+
+            entry.value.ok_or(CacheError::CacheMiss)
+        }
+
+        /// Inserts a new key value pair into this cache. New entries become LIR entries
+        /// while there is room for them, and resident HIR entries otherwise. Non-resident
+        /// entries still in the stack are promoted to LIR entries. If this cache is full,
+        /// the resident HIR entry at the front of the queue is evicted.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            if let Some(entry) = self.entries.get_mut(&key) {
+                if entry.value.is_some() {
+                    entry.value = Some(value);
+                    return self.hit(&key);
+                }
+            }
+
+            if self.resident_len() >= self.capacity() {
+                self.evict()?;
+            }
+
+            if let Some(entry) = self.entries.get_mut(&key) {
+                let link = entry.queue_link.take();
+                entry.value = Some(value);
+                entry.status = LirsStatus::Lir;
+
+                if let Some(link) = link {
+                    self.non_resident
+                        .remove(&link)
+                        .map_err(CacheError::CacheBroken)?;
+                }
+
+                self.lir_count += 1;
+                self.push_to_stack_top(&key)?;
+                return self.demote_excess_lir();
+            }
+
+            let status = if self.lir_count < self.lir_capacity {
+                self.lir_count += 1;
+                LirsStatus::Lir
+            } else {
+                LirsStatus::ResidentHir
+            };
+
+            self.entries.insert(
+                key,
+                LirsEntry {
+                    value: Some(value),
+                    status,
+                    stack_link: None,
+                    queue_link: None,
+                },
+            );
+            self.push_to_stack_top(&key)?;
+
+            if status == LirsStatus::ResidentHir {
+                let link = self.queue.push_back(key).map_err(CacheError::CacheBroken)?;
+                self.entry_mut(&key)?.queue_link = Some(link);
+            }
+
+            Ok(())
+        }
+
+        /// Handles a reference to a resident entry.
+        fn hit(&mut self, key: &K) -> Result<(), CacheError> {
+            let entry = self.entry_mut(key)?;
+
+            match (entry.status, entry.stack_link, entry.queue_link) {
+                (LirsStatus::ResidentHir, Some(_), queue_link) => {
+                    entry.status = LirsStatus::Lir;
+                    entry.queue_link = None;
+
+                    if let Some(link) = queue_link {
+                        self.queue.remove(&link).map_err(CacheError::CacheBroken)?;
+                    }
+
+                    self.lir_count += 1;
+                    self.push_to_stack_top(key)?;
+                    self.demote_excess_lir()
+                }
+                (LirsStatus::ResidentHir, None, queue_link) => {
+                    if let Some(link) = queue_link {
+                        self.queue
+                            .reposition_to_tail(&link)
+                            .map_err(CacheError::CacheBroken)?;
+                    }
+
+                    self.push_to_stack_top(key)
+                }
+                _ => {
+                    self.push_to_stack_top(key)?;
+                    self.prune()
+                }
+            }
+        }
+
+        fn push_to_stack_top(&mut self, key: &K) -> Result<(), CacheError> {
+            match self.entry_mut(key)?.stack_link {
+                Some(link) => self
+                    .stack
+                    .reposition_to_tail(&link)
+                    .map_err(CacheError::CacheBroken),
+                None => {
+                    let link = self
+                        .stack
+                        .push_back(*key)
+                        .map_err(CacheError::CacheBroken)?;
+                    self.entry_mut(key)?.stack_link = Some(link);
+                    Ok(())
+                }
+            }
+        }
+
+        /// Removes HIR entries from the bottom of the stack, until an LIR entry is at
+        /// the bottom. Resident HIR entries stay in the queue, while non-resident entries
+        /// are dropped.
+        fn prune(&mut self) -> Result<(), CacheError> {
+            while let Ok(key) = self.stack.peek_front() {
+                let key = *key;
+                let entry = self.entry_mut(&key)?;
+
+                match entry.status {
+                    LirsStatus::Lir => break,
+                    LirsStatus::ResidentHir => entry.stack_link = None,
+                    LirsStatus::NonResidentHir => {
+                        let entry = self
+                            .entries
+                            .remove(&key)
+                            .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+
+                        if let Some(link) = entry.queue_link {
+                            self.non_resident
+                                .remove(&link)
+                                .map_err(CacheError::CacheBroken)?;
+                        }
+                    }
+                }
+
+                self.stack.pop_front().map_err(CacheError::CacheBroken)?;
+            }
+
+            Ok(())
+        }
+
+        /// Demotes the LIR entry at the bottom of the stack to a resident HIR entry at the
+        /// end of the queue, while there are more LIR entries than there is room for.
+        fn demote_excess_lir(&mut self) -> Result<(), CacheError> {
+            self.prune()?;
+
+            while self.lir_count > self.lir_capacity {
+                self.demote_bottom_lir()?;
+            }
+
+            Ok(())
+        }
+
+        fn demote_bottom_lir(&mut self) -> Result<(), CacheError> {
+            let key = self.stack.pop_front().map_err(CacheError::CacheBroken)?;
+            let link = self.queue.push_back(key).map_err(CacheError::CacheBroken)?;
+
+            let entry = self.entry_mut(&key)?;
+            entry.status = LirsStatus::ResidentHir;
+            entry.stack_link = None;
+            entry.queue_link = Some(link);
+
+            self.lir_count -= 1;
+            self.prune()
+        }
+
+        /// Evicts the resident HIR entry at the front of the queue. It is kept as a
+        /// non-resident entry if it is still in the stack.
+        fn evict(&mut self) -> Result<(), CacheError> {
+            if self.queue.is_empty() {
+                self.demote_bottom_lir()?;
+            }
+
+            let key = self.queue.pop_front().map_err(CacheError::CacheBroken)?;
+            let entry = self.entry_mut(&key)?;
+
+            if entry.stack_link.is_none() {
+                self.entries.remove(&key);
+                return Ok(());
+            }
+
+            entry.value = None;
+            entry.status = LirsStatus::NonResidentHir;
+            entry.queue_link = None;
+
+            if self.non_resident.is_full() {
+                let oldest = self
+                    .non_resident
+                    .pop_front()
+                    .map_err(CacheError::CacheBroken)?;
+                let oldest_entry = self
+                    .entries
+                    .remove(&oldest)
+                    .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+
+                if let Some(link) = oldest_entry.stack_link {
+                    self.stack.remove(&link).map_err(CacheError::CacheBroken)?;
+                }
+            }
+
+            let link = self
+                .non_resident
+                .push_back(key)
+                .map_err(CacheError::CacheBroken)?;
+            self.entry_mut(&key)?.queue_link = Some(link);
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::lrucache::LRUCache;
+
+        fn status(lirs_cache: &LirsCache<i32, i32>, key: i32) -> Option<LirsStatus> {
+            lirs_cache.entries.get(&key).map(|entry| entry.status)
+        }
+
+        #[test]
+        fn lirs_cache_consistency() {
+            let mut lirs_cache = LirsCache::<i32, i32>::with_capacity(0);
+            assert_eq!(
+                lirs_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+
+            let mut lirs_cache = LirsCache::<i32, i32>::with_capacities(2, 1);
+            for ele in 0..3 {
+                assert!(lirs_cache.insert(ele, ele).is_ok());
+            }
+            assert_eq!(status(&lirs_cache, 0), Some(LirsStatus::Lir));
+            assert_eq!(status(&lirs_cache, 1), Some(LirsStatus::Lir));
+            assert_eq!(status(&lirs_cache, 2), Some(LirsStatus::ResidentHir));
+
+            // 2 is evicted, but stays in the stack as a non-resident entry
+            assert!(lirs_cache.insert(3, 3).is_ok());
+            assert_eq!(lirs_cache.query(&2), Err(CacheError::CacheMiss));
+            assert_eq!(status(&lirs_cache, 2), Some(LirsStatus::NonResidentHir));
+
+            // 3 is evicted to make room for 2, which comes back with a smaller recency
+            // than 0, so 0 gets demoted
+            assert!(lirs_cache.insert(2, 2).is_ok());
+            assert_eq!(status(&lirs_cache, 2), Some(LirsStatus::Lir));
+            assert_eq!(status(&lirs_cache, 3), Some(LirsStatus::NonResidentHir));
+            assert_eq!(status(&lirs_cache, 0), Some(LirsStatus::ResidentHir));
+            assert!(lirs_cache.stack.iter().eq([1, 3, 2].iter()));
+            assert!(lirs_cache.queue.iter().eq([0].iter()));
+
+            // 0 is hit while out of the stack, so it stays HIR
+            assert_eq!(lirs_cache.query(&0), Ok(&0));
+            assert_eq!(status(&lirs_cache, 0), Some(LirsStatus::ResidentHir));
+            assert!(lirs_cache.stack.iter().eq([1, 3, 2, 0].iter()));
+
+            // 0 is hit again while in the stack, so it becomes LIR, demoting 1 and
+            // pruning 3 from the stack
+            assert_eq!(lirs_cache.query(&0), Ok(&0));
+            assert_eq!(status(&lirs_cache, 3), None);
+            assert_eq!(status(&lirs_cache, 0), Some(LirsStatus::Lir));
+            assert_eq!(status(&lirs_cache, 1), Some(LirsStatus::ResidentHir));
+            assert!(lirs_cache.stack.iter().eq([2, 0].iter()));
+
+            assert!(lirs_cache.insert(0, -1).is_ok());
+            assert_eq!(lirs_cache.query(&0), Ok(&-1));
+
+            assert_eq!(lirs_cache.remove(&2), Ok(2));
+            assert!(lirs_cache.stack.iter().eq([0].iter()));
+            assert_eq!(lirs_cache.remove(&2), Err(CacheError::CacheMiss));
+            assert_eq!(lirs_cache.lir_count, 1);
+        }
+
+        #[test]
+        fn lirs_cache_remove_non_resident() {
+            let mut lirs_cache = LirsCache::<i32, i32>::with_capacities(2, 1);
+
+            assert!(lirs_cache.insert(0, 0).is_ok());
+            assert!(lirs_cache.insert(1, 1).is_ok());
+            assert!(lirs_cache.insert(2, 2).is_ok());
+            assert!(lirs_cache.insert(3, 3).is_ok());
+            assert_eq!(status(&lirs_cache, 2), Some(LirsStatus::NonResidentHir));
+
+            // removing a non-resident entry misses, but keeps its history
+            assert_eq!(lirs_cache.remove(&2), Err(CacheError::CacheMiss));
+            assert_eq!(status(&lirs_cache, 2), Some(LirsStatus::NonResidentHir));
+
+            assert!(lirs_cache.insert(2, -2).is_ok());
+            assert_eq!(status(&lirs_cache, 2), Some(LirsStatus::Lir));
+            assert_eq!(lirs_cache.remove(&2), Ok(-2));
+            assert_eq!(lirs_cache.remove(&2), Err(CacheError::CacheMiss));
+        }
+
+        #[test]
+        fn lirs_cache_looping_access() {
+            let mut lirs_cache = LirsCache::<i32, i32>::with_capacities(2, 1);
+            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(3);
+
+            let (mut lirs_hits, mut lru_hits) = (0, 0);
+            for _ in 0..100 {
+                for ele in 0..4 {
+                    match lirs_cache.query(&ele) {
+                        Ok(_) => lirs_hits += 1,
+                        Err(_) => lirs_cache.insert(ele, ele).unwrap(),
+                    }
+
+                    match lru_cache.query(&ele) {
+                        Ok(_) => lru_hits += 1,
+                        Err(_) => lru_cache.insert(ele, ele).unwrap(),
+                    }
+                }
+
+                assert_eq!(lirs_cache.resident_len(), 3);
+                assert!(lirs_cache.non_resident.len() <= 3);
+            }
+
+            assert_eq!(lru_hits, 0);
+            assert!(lirs_hits >= 2 * 99);
+        }
+    }
+}
+
+pub mod lruk {
+    //! Module providing an LRU-K Cache implementation.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::lruk::LruKCache;
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! let mut lruk_cache = LruKCache::<i32, i32>::with_capacity(3);
+    //!
+    //! assert!(lruk_cache.insert(0, 0).is_ok());
+    //! assert!(lruk_cache.insert(1, 1).is_ok());
+    //! assert_eq!(lruk_cache.query(&0), Ok(&0));
+    //! assert_eq!(lruk_cache.query(&1), Ok(&1));
+    //!
+    //! // a scan only ever references keys once, and does not flush out 0 and 1
+    //! for ele in 2..100 {
+    //!     assert!(lruk_cache.insert(ele, ele).is_ok());
+    //! }
+    //!
+    //! assert_eq!(lruk_cache.query(&0), Ok(&0));
+    //! assert_eq!(lruk_cache.query(&1), Ok(&1));
+    //! assert_eq!(lruk_cache.query(&2), Err(CacheError::CacheMiss));
+    //!
+    //! assert_eq!(lruk_cache.remove(&0), Ok(0));
+    //! assert_eq!(lruk_cache.remove(&0), Err(CacheError::CacheMiss));
+    //!
+    //! // zero capacity LruKCache is unusable
+    //! let mut lruk_cache = LruKCache::<i32, i32>::with_capacity(0);
+    //!
+    //! assert!(matches!(
+    //!     lruk_cache.insert(0, 0),
+    //!     Err(CacheError::CacheBroken(_))
+    //! ));
+    //! ```
+
+    use crate::arena::{Arena, Index};
+    use crate::list::{Link, LinkedList, ListError};
+    use crate::lrucache::CacheError;
+    use std::{
+        collections::{BTreeMap, HashMap},
+        hash::Hash,
+    };
+
+    /// Reference history of a key. `refs[i]` is the time of the (i + 1)-th most recent
+    /// uncorrelated reference, with 0 standing for no reference. `last` is the time of
+    /// the most recent reference, correlated or not.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct History {
+        pub refs: Vec<u64>,
+        pub last: u64,
+    }
+
+    impl History {
+        fn new(k: usize) -> Self {
+            History {
+                refs: vec![0; k],
+                last: 0,
+            }
+        }
+
+        /// Records an uncorrelated reference at time t. The history is shifted along by
+        /// the length of the previous correlated reference period, so that a burst of
+        /// correlated references counts as a single reference.
+        fn record(&mut self, t: u64) {
+            let correlated_period = self.last - self.refs[0];
+            for i in (1..self.refs.len()).rev() {
+                self.refs[i] = if self.refs[i - 1] == 0 {
+                    0
+                } else {
+                    self.refs[i - 1] + correlated_period
+                };
+            }
+
+            self.refs[0] = t;
+            self.last = t;
+        }
+
+        /// Key ordering entries by their K-th most recent reference, and then by their
+        /// most recent uncorrelated reference.
+        fn priority(&self) -> (u64, u64) {
+            (self.refs[self.refs.len() - 1], self.refs[0])
+        }
+    }
+
+    /// Cache block storing some key and value, along with its reference history.
+    pub struct LruKBlock<K, V> {
+        pub key: K,
+        pub value: V,
+        pub history: History,
+    }
+
+    /// An LRU-K Cache implemented using a generational arena, a hash map and an ordered
+    /// map over the reference histories. The entry evicted is the one with the oldest
+    /// K-th most recent reference, entries with less than K references going first.
+    /// References within the correlated reference period of the last one are
+    /// considered correlated, and do not count as new references. Entries referenced
+    /// within the correlated reference period are not evicted, if possible.
+    ///
+    /// The reference histories of evicted keys are retained in a bounded table, oldest
+    /// first out, and restored when the key is inserted again.
+    ///
+    /// This follows "The LRU-K Page Replacement Algorithm For Database Disk Buffering" by
+    /// Elizabeth J. O'Neil, Patrick E. O'Neil and Gerhard Weikum.
+    pub struct LruKCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        blocks: Arena<LruKBlock<K, V>>,
+        block_refs: HashMap<K, Index>,
+        priorities: BTreeMap<(u64, u64), Index>,
+
+        retained: HashMap<K, (History, Link)>,
+        retained_order: LinkedList<K>,
+
+        k: usize,
+        correlated_period: u64,
+        time: u64,
+    }
+
+    impl<K, V> LruKCache<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        /// Creates an LRU-2 Cache instance with the given capacity, no correlated
+        /// reference period, and retaining the histories of as many evicted keys as its
+        /// capacity. A zero capacity LruKCache is unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            Self::with_parameters(capacity, 2, 0, capacity)
+        }
+
+        /// Creates an LRU-K Cache instance with the given capacity, value of K (at least
+        /// 1), correlated reference period counted in references to this cache, and
+        /// number of evicted keys to retain the histories of.
+        pub fn with_parameters(
+            capacity: usize,
+            k: usize,
+            correlated_period: u64,
+            history_capacity: usize,
+        ) -> Self {
+            LruKCache {
+                blocks: Arena::with_capacity(capacity),
+                block_refs: HashMap::new(),
+                priorities: BTreeMap::new(),
+                retained: HashMap::new(),
+                retained_order: LinkedList::with_capacity(history_capacity),
+                k: k.max(1),
+                correlated_period,
+                time: 0,
+            }
+        }
+
+        /// Returns the reference history of the given key, whether resident or retained.
+        pub fn history(&self, key: &K) -> Option<&History> {
+            match self.block_refs.get(key) {
+                Some(index) => self.blocks.get(index).map(|block| &block.history),
+                None => self.retained.get(key).map(|(history, _)| history),
+            }
+        }
+
+        /// Returns a reference to the value associated with the given key, recording a
+        /// reference to it. If the key is not present in the cache, we return a
+        /// "cache-miss" error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let index = *self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            self.time += 1;
+            self.reference(&index)?;
+
+            let block = self
+                .blocks
+                .get(&index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            Ok(&block.value)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache, along
+        /// with its reference history. If no entry is found, we return a "cache-miss"
+        /// error. Returns the value associated, after removal with ownership.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            if let Some((_, link)) = self.retained.remove(key) {
+                self.retained_order
+                    .remove(&link)
+                    .map_err(CacheError::CacheBroken)?;
+            }
+
+            let index = self.block_refs.remove(key).ok_or(CacheError::CacheMiss)?;
+            let block = self
+                .blocks
+                .remove(&index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            self.priorities.remove(&block.history.priority());
+
+            Ok(block.value)
+        }
+
+        /// Inserts a new key value pair into this cache, recording a reference to it. If
+        /// this cache is full, the entry with the oldest K-th most recent reference is
+        /// evicted.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            self.time += 1;
+
+            if let Some(index) = self.block_refs.get(&key).copied() {
+                self.reference(&index)?;
+                let block = self
+                    .blocks
+                    .get_mut(&index)
+                    .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+                block.value = value;
+                return Ok(());
+            }
+
+            if self.block_refs.len() == self.blocks.capacity() {
+                self.evict()?;
+            }
+
+            let mut history = match self.retained.remove(&key) {
+                Some((history, link)) => {
+                    self.retained_order
+                        .remove(&link)
+                        .map_err(CacheError::CacheBroken)?;
+                    history
+                }
+                None => History::new(self.k),
+            };
+            history.record(self.time);
+
+            let priority = history.priority();
+            let index = self
+                .blocks
+                .insert(LruKBlock {
+                    key,
+                    value,
+                    history,
+                })
+                .map_err(|arena_oom| CacheError::CacheBroken(ListError::ListOOM(arena_oom)))?;
+            self.block_refs.insert(key, index);
+            self.priorities.insert(priority, index);
+
+            Ok(())
+        }
+
+        fn reference(&mut self, index: &Index) -> Result<(), CacheError> {
+            let block = self
+                .blocks
+                .get_mut(index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            let history = &mut block.history;
+
+            if self.time - history.last <= self.correlated_period {
+                history.last = self.time;
+                return Ok(());
+            }
+
+            self.priorities.remove(&history.priority());
+            history.record(self.time);
+            self.priorities.insert(history.priority(), *index);
+
+            Ok(())
+        }
+
+        fn evict(&mut self) -> Result<(), CacheError> {
+            let victim = self
+                .priorities
+                .iter()
+                .find(|(_, index)| {
+                    self.blocks.get(index).is_some_and(|block| {
+                        self.time - block.history.last > self.correlated_period
+                    })
+                })
+                .or_else(|| self.priorities.iter().next())
+                .map(|(priority, index)| (*priority, *index))
+                .ok_or(CacheError::CacheBroken(ListError::ListEmpty))?;
+
+            let (priority, index) = victim;
+            self.priorities.remove(&priority);
+            let block = self
+                .blocks
+                .remove(&index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            self.block_refs.remove(&block.key);
+
+            if self.retained_order.capacity() == 0 {
+                return Ok(());
+            }
+
+            if self.retained_order.is_full() {
+                let oldest = self
+                    .retained_order
+                    .pop_front()
+                    .map_err(CacheError::CacheBroken)?;
+                self.retained.remove(&oldest);
+            }
+
+            let link = self
+                .retained_order
+                .push_back(block.key)
+                .map_err(CacheError::CacheBroken)?;
+            self.retained.insert(block.key, (block.history, link));
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn lruk_cache_consistency() {
+            let mut lruk_cache = LruKCache::<i32, i32>::with_capacity(0);
+            assert_eq!(
+                lruk_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+
+            let mut lruk_cache = LruKCache::<i32, i32>::with_capacity(2);
+            assert!(lruk_cache.insert(0, 0).is_ok());
+            assert!(lruk_cache.insert(1, 1).is_ok());
+            assert_eq!(lruk_cache.query(&0), Ok(&0));
+            assert_eq!(
+                lruk_cache.history(&0),
+                Some(&History {
+                    refs: vec![3, 1],
+                    last: 3
+                })
+            );
+
+            // 1 has a single reference, so it is evicted even though 0 is older
+            assert!(lruk_cache.insert(2, 2).is_ok());
+            assert_eq!(lruk_cache.query(&1), Err(CacheError::CacheMiss));
+
+            // the history of 1 is retained, and restored on insertion
+            assert_eq!(
+                lruk_cache.history(&1),
+                Some(&History {
+                    refs: vec![2, 0],
+                    last: 2
+                })
+            );
+            assert!(lruk_cache.insert(1, 1).is_ok());
+            assert_eq!(
+                lruk_cache.history(&1),
+                Some(&History {
+                    refs: vec![5, 2],
+                    last: 5
+                })
+            );
+            assert_eq!(lruk_cache.query(&2), Err(CacheError::CacheMiss));
+
+            // 0 now has the oldest second most recent reference
+            assert!(lruk_cache.insert(3, 3).is_ok());
+            assert_eq!(lruk_cache.query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(lruk_cache.query(&1), Ok(&1));
+
+            assert!(lruk_cache.insert(1, -1).is_ok());
+            assert_eq!(lruk_cache.query(&1), Ok(&-1));
+            assert_eq!(lruk_cache.remove(&1), Ok(-1));
+            assert_eq!(lruk_cache.remove(&1), Err(CacheError::CacheMiss));
+            assert_eq!(lruk_cache.history(&1), None);
+            assert_eq!(lruk_cache.priorities.len(), 1);
+        }
+
+        #[test]
+        fn lruk_cache_correlated_references() {
+            let mut lruk_cache = LruKCache::<i32, i32>::with_parameters(3, 2, 2, 1);
+            assert!(lruk_cache.insert(0, 0).is_ok());
+            assert!(lruk_cache.insert(1, 1).is_ok());
+
+            // correlated references only move the last reference
+            assert_eq!(lruk_cache.query(&0), Ok(&0));
+            assert_eq!(
+                lruk_cache.history(&0),
+                Some(&History {
+                    refs: vec![1, 0],
+                    last: 3
+                })
+            );
+
+            // 0 is within its correlated reference period, so 1 is evicted instead
+            assert!(lruk_cache.insert(2, 2).is_ok());
+            assert!(lruk_cache.insert(3, 3).is_ok());
+            assert_eq!(lruk_cache.query(&1), Err(CacheError::CacheMiss));
+
+            // an uncorrelated reference shifts the history by the correlated period
+            assert_eq!(lruk_cache.query(&0), Ok(&0));
+            assert_eq!(
+                lruk_cache.history(&0),
+                Some(&History {
+                    refs: vec![6, 3],
+                    last: 6
+                })
+            );
+
+            // only one evicted history is retained
+            assert!(lruk_cache.insert(4, 4).is_ok());
+            assert_eq!(lruk_cache.query(&2), Err(CacheError::CacheMiss));
+            assert_eq!(lruk_cache.history(&1), None);
+            assert!(lruk_cache.history(&2).is_some());
+        }
+    }
+}
+
+pub mod gdsf {
+    //! Module providing a GreedyDual-Size-Frequency (GDSF) Cache implementation.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::gdsf::{GdsfCache, GdsfError};
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! // total size of the entries is at most 10
+    //! let mut gdsf_cache = GdsfCache::<i32, i32>::with_capacity(10);
+    //!
+    //! // key, value, cost, size
+    //! assert!(gdsf_cache.insert(0, 0, 1.0, 5).is_ok());
+    //! assert!(gdsf_cache.insert(1, 1, 10.0, 5).is_ok());
+    //!
+    //! // 0 is cheaper to recompute per unit of size than 1
+    //! assert!(gdsf_cache.insert(2, 2, 1.0, 1).is_ok());
+    //! assert_eq!(gdsf_cache.query(&0), Err(CacheError::CacheMiss));
+    //! assert_eq!(gdsf_cache.query(&1), Ok(&1));
+    //!
+    //! assert_eq!(gdsf_cache.remove(&2), Ok(2));
+    //! assert_eq!(gdsf_cache.remove(&2), Err(CacheError::CacheMiss));
+    //!
+    //! assert_eq!(
+    //!     gdsf_cache.insert(3, 3, 1.0, 11),
+    //!     Err(GdsfError::EntryTooLarge)
+    //! );
+    //! assert_eq!(
+    //!     gdsf_cache.insert(3, 3, f64::NAN, 1),
+    //!     Err(GdsfError::InvalidCost)
+    //! );
+    //! ```
+
+    use crate::arena::{Arena, Index};
+    use crate::list::ListError;
+    use crate::lrucache::CacheError;
+    use std::{
+        cmp::Ordering,
+        collections::{BTreeMap, HashMap},
+        fmt::Display,
+        hash::Hash,
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum GdsfError {
+        Cache(CacheError),
+        EntryTooLarge,
+        InvalidCost,
+    }
+
+    impl Display for GdsfError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match &self {
+                GdsfError::Cache(cache_error) => cache_error.fmt(f),
+                GdsfError::EntryTooLarge => write!(f, "Entry does not fit in the cache."),
+                GdsfError::InvalidCost => write!(f, "Entry cost is not a positive number."),
+            }
+        }
+    }
+
+    impl From<CacheError> for GdsfError {
+        fn from(cache_error: CacheError) -> Self {
+            GdsfError::Cache(cache_error)
+        }
+    }
+
+    /// Cache block storing some key and value, along with its cost, size, access
+    /// frequency and priority. Ties in priority are broken by the tick of the last
+    /// access.
+    pub struct GdsfBlock<K, V> {
+        pub key: K,
+        pub value: V,
+        pub cost: f64,
+        pub size: usize,
+        pub freq: u64,
+        pub priority: f64,
+        pub tick: u64,
+    }
+
+    /// Totally ordered priority, for use as a key in ordered maps.
+    #[derive(Debug, Clone, Copy)]
+    struct Priority(f64);
+
+    impl PartialEq for Priority {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == Ordering::Equal
+        }
+    }
+
+    impl Eq for Priority {}
+
+    impl PartialOrd for Priority {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Priority {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.0.total_cmp(&other.0)
+        }
+    }
+
+    /// A GreedyDual-Size-Frequency (GDSF) Cache implemented using a generational arena, a
+    /// hash map and an ordered map over the entry priorities. Every entry carries a cost
+    /// and a size, and its priority is `L + freq * cost / size`. The entry with the lowest
+    /// priority is evicted, with the inflation value `L` rising to its priority, so that
+    /// entries not accessed in a while age relative to newer ones.
+    ///
+    /// The capacity of this cache bounds the total size of its entries.
+    ///
+    /// This follows "Role of Aging, Frequency, and Size in Web Cache Replacement Policies"
+    /// by Ludmila Cherkasova and Gianfranco Ciardo.
+    pub struct GdsfCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        blocks: Arena<GdsfBlock<K, V>>,
+        block_refs: HashMap<K, Index>,
+        priorities: BTreeMap<(Priority, u64), Index>,
+
+        capacity: usize,
+        size: usize,
+        inflation: f64,
+        tick: u64,
+    }
+
+    impl<K, V> GdsfCache<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        /// Creates a GdsfCache instance bounding the total size of its entries by the
+        /// given capacity.
+        pub fn with_capacity(capacity: usize) -> Self {
+            GdsfCache {
+                blocks: Arena::new(),
+                block_refs: HashMap::new(),
+                priorities: BTreeMap::new(),
+                capacity,
+                size: 0,
+                inflation: 0.0,
+                tick: 0,
+            }
+        }
+
+        /// Returns the total size of the entries in this cache.
+        pub fn size(&self) -> usize {
+            self.size
+        }
+
+        /// Returns the current inflation value `L`.
+        pub fn inflation(&self) -> f64 {
+            self.inflation
+        }
+
+        /// Returns a reference to the value associated with the given key, raising its
+        /// priority. If the key is not present in the cache, we return a "cache-miss"
+        /// error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let index = *self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            self.tick += 1;
+
+            let block = self
+                .blocks
+                .get_mut(&index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            self.priorities
+                .remove(&(Priority(block.priority), block.tick));
+
+            block.freq += 1;
+            block.priority = self.inflation + block.freq as f64 * block.cost / block.size as f64;
+            block.tick = self.tick;
+            self.priorities
+                .insert((Priority(block.priority), block.tick), index);
+
+            Ok(&block.value)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error. Returns the value associated,
+        /// after removal with ownership.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            let index = *self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            Ok(self.remove_block(&index)?.value)
+        }
+
+        fn remove_block(&mut self, index: &Index) -> Result<GdsfBlock<K, V>, CacheError> {
+            let block = self
+                .blocks
+                .remove(index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            self.block_refs.remove(&block.key);
+            self.priorities
+                .remove(&(Priority(block.priority), block.tick));
+            self.size -= block.size;
+
+            Ok(block)
+        }
+
+        /// Inserts a new key value pair with the given cost and size (at least 1) into
+        /// this cache. Updating an existing entry counts as an access to it. Entries with
+        /// the lowest priority are evicted until the new entry fits. If the entry can
+        /// never fit, we return an "entry-too-large" error. The cost must be finite and
+        /// positive, otherwise we return an "invalid-cost" error.
+        pub fn insert(
+            &mut self,
+            key: K,
+            value: V,
+            cost: f64,
+            size: usize,
+        ) -> Result<(), GdsfError> {
+            if !cost.is_finite() || cost <= 0.0 {
+                return Err(GdsfError::InvalidCost);
+            }
+
+            let size = size.max(1);
+            if size > self.capacity {
+                return Err(GdsfError::EntryTooLarge);
+            }
+
+            let freq = match self.block_refs.get(&key).copied() {
+                Some(index) => self.remove_block(&index)?.freq + 1,
+                None => 1,
+            };
+
+            while self.size + size > self.capacity {
+                let (_, index) = self.priorities.pop_first().ok_or(GdsfError::Cache(
+                    CacheError::CacheBroken(ListError::ListEmpty),
+                ))?;
+                let block = self.remove_block(&index)?;
+                self.inflation = block.priority;
+            }
+
+            if self.block_refs.len() == self.blocks.capacity() {
+                self.blocks.reserve(self.blocks.capacity().max(1));
+            }
+
+            self.tick += 1;
+            let priority = self.inflation + freq as f64 * cost / size as f64;
+            let index = self
+                .blocks
+                .insert(GdsfBlock {
+                    key,
+                    value,
+                    cost,
+                    size,
+                    freq,
+                    priority,
+                    tick: self.tick,
+                })
+                .map_err(|arena_oom| CacheError::CacheBroken(ListError::ListOOM(arena_oom)))?;
+            self.block_refs.insert(key, index);
+            self.priorities
+                .insert((Priority(priority), self.tick), index);
+            self.size += size;
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn gdsf_cache_consistency() {
+            let mut gdsf_cache = GdsfCache::<i32, i32>::with_capacity(0);
+            assert_eq!(
+                gdsf_cache.insert(0, 0, 1.0, 1),
+                Err(GdsfError::EntryTooLarge)
+            );
+
+            let mut gdsf_cache = GdsfCache::<i32, i32>::with_capacity(10);
+            assert!(gdsf_cache.insert(0, 0, 1.0, 5).is_ok());
+            assert!(gdsf_cache.insert(1, 1, 10.0, 5).is_ok());
+            assert_eq!(gdsf_cache.size(), 10);
+
+            // 0 has the lowest priority, 1 * 1.0 / 5
+            assert!(gdsf_cache.insert(2, 2, 1.0, 1).is_ok());
+            assert_eq!(gdsf_cache.query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(gdsf_cache.inflation(), 0.2);
+
+            // 2 is accessed twice more, for a priority of 0.2 + 3 * 1.0 / 1
+            assert_eq!(gdsf_cache.query(&2), Ok(&2));
+            assert_eq!(gdsf_cache.query(&2), Ok(&2));
+
+            // 1 has a lower priority, 0.0 + 1 * 10.0 / 5
+            assert!(gdsf_cache.insert(3, 3, 1.0, 5).is_ok());
+            assert_eq!(gdsf_cache.query(&1), Err(CacheError::CacheMiss));
+            assert_eq!(gdsf_cache.inflation(), 2.0);
+            assert_eq!(gdsf_cache.size(), 6);
+
+            // updating 3 with a larger size evicts 2, but never 3 itself
+            assert!(gdsf_cache.insert(3, -3, 1.0, 10).is_ok());
+            assert_eq!(gdsf_cache.query(&2), Err(CacheError::CacheMiss));
+            assert_eq!(gdsf_cache.query(&3), Ok(&-3));
+            assert_eq!(gdsf_cache.size(), 10);
+
+            let index = gdsf_cache.block_refs[&3];
+            assert_eq!(gdsf_cache.blocks.get(&index).unwrap().freq, 3);
+
+            // entries of equal priority go least recently used first
+            let mut gdsf_cache = GdsfCache::<i32, i32>::with_capacity(3);
+            for ele in 0..3 {
+                assert!(gdsf_cache.insert(ele, ele, 1.0, 1).is_ok());
+            }
+            assert!(gdsf_cache.insert(3, 3, 1.0, 1).is_ok());
+            assert_eq!(gdsf_cache.query(&0), Err(CacheError::CacheMiss));
+
+            assert_eq!(gdsf_cache.remove(&3), Ok(3));
+            assert_eq!(gdsf_cache.remove(&3), Err(CacheError::CacheMiss));
+            assert_eq!(gdsf_cache.size(), 2);
+            assert_eq!(gdsf_cache.priorities.len(), 2);
+
+            // costs that would break the priority order are rejected, leaving 2 in place
+            for cost in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+                assert_eq!(
+                    gdsf_cache.insert(2, -2, cost, 1),
+                    Err(GdsfError::InvalidCost)
+                );
+            }
+            assert_eq!(gdsf_cache.query(&2), Ok(&2));
+            assert_eq!(gdsf_cache.size(), 2);
+        }
+
+        #[test]
+        fn gdsf_cache_zero_capacity() {
+            let mut gdsf_cache = GdsfCache::<i32, i32>::with_capacity(0);
+            assert_eq!(gdsf_cache.query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(gdsf_cache.remove(&0), Err(CacheError::CacheMiss));
+
+            // sizes are at least 1, so even an empty entry never fits
+            assert_eq!(
+                gdsf_cache.insert(0, 0, 1.0, 0),
+                Err(GdsfError::EntryTooLarge)
+            );
+            assert_eq!(gdsf_cache.insert(0, 0, 0.0, 0), Err(GdsfError::InvalidCost));
+            assert_eq!(gdsf_cache.size(), 0);
+            assert!(gdsf_cache.block_refs.is_empty());
+            assert!(gdsf_cache.priorities.is_empty());
+        }
+
+        #[test]
+        fn gdsf_cache_repeated_keys() {
+            let mut gdsf_cache = GdsfCache::<i32, i32>::with_capacity(4);
+            for ele in 0..3 {
+                assert!(gdsf_cache.insert(0, ele, 1.0, 1).is_ok());
+            }
+            assert_eq!(gdsf_cache.size(), 1);
+            assert_eq!(gdsf_cache.priorities.len(), 1);
+            assert_eq!(gdsf_cache.query(&0), Ok(&2));
+
+            let index = gdsf_cache.block_refs[&0];
+            assert_eq!(gdsf_cache.blocks.get(&index).unwrap().freq, 4);
+
+            // a repeated key that can never fit leaves the existing entry in place
+            assert_eq!(
+                gdsf_cache.insert(0, -1, 1.0, 5),
+                Err(GdsfError::EntryTooLarge)
+            );
+            assert_eq!(gdsf_cache.query(&0), Ok(&2));
+            assert_eq!(gdsf_cache.size(), 1);
+
+            // growing a repeated key to the full capacity evicts everything else
+            assert!(gdsf_cache.insert(1, 1, 1.0, 3).is_ok());
+            assert!(gdsf_cache.insert(0, 0, 1.0, 4).is_ok());
+            assert_eq!(gdsf_cache.query(&1), Err(CacheError::CacheMiss));
+            assert_eq!(gdsf_cache.query(&0), Ok(&0));
+            assert_eq!(gdsf_cache.size(), 4);
+            assert_eq!(gdsf_cache.priorities.len(), 1);
+        }
+    }
+}
+
+mod rng {
+    //! Module providing a small pseudo-random number generator, for cache policies making
+    //! randomized choices.
+
+    /// A xorshift64 pseudo-random number generator. Not suitable for cryptographic use.
+    pub(crate) struct XorShift64 {
+        state: u64,
+    }
+
+    impl XorShift64 {
+        pub(crate) fn with_seed(seed: u64) -> Self {
+            XorShift64 { state: seed.max(1) }
+        }
+
+        pub(crate) fn next_u64(&mut self) -> u64 {
+            self.state ^= self.state << 13;
+            self.state ^= self.state >> 7;
+            self.state ^= self.state << 17;
+            self.state
+        }
+
+        /// Returns a number in `0..bound`. The bound must be non-zero.
+        pub(crate) fn next_below(&mut self, bound: usize) -> usize {
+            (self.next_u64() % bound as u64) as usize
+        }
+
+        /// Returns a number in `[0, 1)`.
+        pub(crate) fn next_f64(&mut self) -> f64 {
+            (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+}
+
+pub mod sampled {
+    //! Module providing a sampled, approximate Least-Recently-Used Cache implementation,
+    //! in the manner of Redis.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::sampled::SampledLruCache;
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! let capacity = 100;
+    //! let mut sampled_cache = SampledLruCache::<i32, i32>::with_capacity(capacity);
+    //!
+    //! for ele in 0..capacity as i32 {
+    //!     assert!(sampled_cache.insert(ele, ele).is_ok());
+    //! }
+    //!
+    //! assert_eq!(sampled_cache.query(&0), Ok(&0));
+    //!
+    //! // one of the older entries is evicted, but not the one just used
+    //! assert!(sampled_cache.insert(100, 100).is_ok());
+    //! assert_eq!(sampled_cache.query(&0), Ok(&0));
+    //! assert_eq!(sampled_cache.len(), capacity);
+    //!
+    //! assert_eq!(sampled_cache.remove(&0), Ok(0));
+    //! assert_eq!(sampled_cache.remove(&0), Err(CacheError::CacheMiss));
+    //!
+    //! // zero capacity SampledLruCache is unusable
+    //! let mut sampled_cache = SampledLruCache::<i32, i32>::with_capacity(0);
+    //!
+    //! assert!(matches!(
+    //!     sampled_cache.insert(0, 0),
+    //!     Err(CacheError::CacheBroken(_))
+    //! ));
+    //! ```
+
+    use crate::arena::{Arena, Index};
+    use crate::list::ListError;
+    use crate::lrucache::CacheError;
+    use crate::rng::XorShift64;
+    use std::{collections::HashMap, hash::Hash};
+
+    /// Default number of slots sampled per eviction.
+    pub const DEFAULT_SAMPLES: usize = 5;
+
+    /// Default number of eviction candidates carried between evictions.
+    pub const DEFAULT_POOL_SIZE: usize = 16;
+
+    /// Cache block storing some key and value, along with the stamp of its last access.
+    pub struct SampledBlock<K, V> {
+        pub key: K,
+        pub value: V,
+        pub stamp: u64,
+    }
+
+    /// An approximate Least-Recently-Used Cache implemented using a generational arena and
+    /// a hash map. Entries only store the stamp of their last access, instead of links
+    /// to their neighbours in recency order. To evict, a number of arena slots are
+    /// sampled at random, and the entry with the oldest stamp is chosen.
+    ///
+    /// An eviction pool carries the oldest candidates seen across evictions. Candidates
+    /// accessed or removed since being sampled are detected through their stamp and
+    /// arena generation, and discarded. More samples and a larger pool bring the choice
+    /// of victim closer to that of an exact LRU, at the cost of slower evictions.
+    pub struct SampledLruCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        blocks: Arena<SampledBlock<K, V>>,
+        block_refs: HashMap<K, Index>,
+        pool: Vec<(u64, Index)>,
+
+        samples: usize,
+        pool_size: usize,
+
+        clock: u64,
+        rng: XorShift64,
+    }
+
+    impl<K, V> SampledLruCache<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        /// Creates a SampledLruCache instance with the given capacity, using the default
+        /// number of samples and pool size. A zero capacity SampledLruCache is unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            Self::with_parameters(capacity, DEFAULT_SAMPLES, DEFAULT_POOL_SIZE)
+        }
+
+        /// Creates a SampledLruCache instance with the given capacity, number of slots
+        /// sampled per eviction and eviction pool size, both at least 1.
+        pub fn with_parameters(capacity: usize, samples: usize, pool_size: usize) -> Self {
+            SampledLruCache {
+                blocks: Arena::with_capacity(capacity),
+                block_refs: HashMap::new(),
+                pool: Vec::with_capacity(pool_size.max(1)),
+                samples: samples.max(1),
+                pool_size: pool_size.max(1),
+                clock: 0,
+                rng: XorShift64::with_seed(0x2545_f491_4f6c_dd1d),
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.block_refs.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.block_refs.is_empty()
+        }
+
+        /// Returns a reference to the value associated with the given key, stamping its
+        /// entry as accessed. If the key is not present in the cache, we return a
+        /// "cache-miss" error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let index = self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            let block = self
+                .blocks
+                .get_mut(index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+
+            self.clock += 1;
+            block.stamp = self.clock;
+
+            Ok(&block.value)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error. Returns the value associated,
+        /// after removal with ownership.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            let index = self.block_refs.remove(key).ok_or(CacheError::CacheMiss)?;
+            let block = self
+                .blocks
+                .remove(&index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            Ok(block.value)
+        }
+
+        /// Inserts a new key value pair into this cache. If this cache is full, the oldest
+        /// entry among the sampled ones and the eviction pool is removed.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            self.clock += 1;
+
+            if let Some(index) = self.block_refs.get(&key) {
+                let block = self
+                    .blocks
+                    .get_mut(index)
+                    .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+                block.value = value;
+                block.stamp = self.clock;
+                return Ok(());
+            }
+
+            if self.block_refs.len() == self.blocks.capacity() {
+                self.evict()?;
+            }
+
+            let index = self
+                .blocks
+                .insert(SampledBlock {
+                    key,
+                    value,
+                    stamp: self.clock,
+                })
+                .map_err(|arena_oom| CacheError::CacheBroken(ListError::ListOOM(arena_oom)))?;
+            self.block_refs.insert(key, index);
+
+            Ok(())
+        }
+
+        /// Samples occupied arena slots into the eviction pool, which is kept sorted with
+        /// the oldest candidate first.
+        fn populate_pool(&mut self) {
+            let capacity = self.blocks.capacity();
+
+            let blocks = &self.blocks;
+            self.pool.retain(|(stamp, index)| {
+                blocks.get(index).is_some_and(|block| &block.stamp == stamp)
+            });
+
+            for _ in 0..self.samples {
+                let candidate = self
+                    .blocks
+                    .index_at(self.rng.next_below(capacity))
+                    .and_then(|index| Some((self.blocks.get(&index)?.stamp, index)));
+
+                let (stamp, index) = match candidate {
+                    Some(candidate) => candidate,
+                    None => continue,
+                };
+
+                if self.pool.iter().any(|(_, pooled)| pooled == &index) {
+                    continue;
+                }
+
+                if self.pool.len() == self.pool_size {
+                    match self.pool.last() {
+                        Some((worst, _)) if stamp < *worst => self.pool.pop(),
+                        _ => continue,
+                    };
+                }
+
+                let position = self.pool.partition_point(|(pooled, _)| pooled < &stamp);
+                self.pool.insert(position, (stamp, index));
+            }
+        }
+
+        fn evict(&mut self) -> Result<(), CacheError> {
+            if self.block_refs.is_empty() {
+                return Err(CacheError::CacheBroken(ListError::ListEmpty));
+            }
+
+            // the pool stays empty if samples only land on free slots
+            loop {
+                self.populate_pool();
+                if !self.pool.is_empty() {
+                    break;
+                }
+            }
+
+            let (_, index) = self.pool.remove(0);
+            let block = self
+                .blocks
+                .remove(&index)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            self.block_refs.remove(&block.key);
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn sampled_lru_cache_consistency() {
+            let mut sampled_cache = SampledLruCache::<i32, i32>::with_capacity(0);
+            assert_eq!(
+                sampled_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+
+            // with enough samples, the least recently used entry is always found
+            let capacity = 4;
+            let mut sampled_cache = SampledLruCache::<i32, i32>::with_parameters(capacity, 64, 2);
+            for ele in 0..capacity as i32 {
+                assert!(sampled_cache.insert(ele, ele).is_ok());
+            }
+            assert_eq!(sampled_cache.query(&0), Ok(&0));
+            assert_eq!(sampled_cache.query(&2), Ok(&2));
+
+            assert!(sampled_cache.insert(4, 4).is_ok());
+            assert_eq!(sampled_cache.query(&1), Err(CacheError::CacheMiss));
+
+            // the runner-up is carried over in the pool
+            let index_3 = sampled_cache.block_refs[&3];
+            assert_eq!(sampled_cache.pool, vec![(4, index_3)]);
+
+            // once accessed, it is discarded from the pool on the next eviction
+            assert_eq!(sampled_cache.query(&3), Ok(&3));
+            assert!(sampled_cache.insert(5, 5).is_ok());
+            assert_eq!(sampled_cache.query(&0), Err(CacheError::CacheMiss));
+            let index_2 = sampled_cache.block_refs[&2];
+            assert_eq!(sampled_cache.pool, vec![(6, index_2)]);
+
+            assert!(sampled_cache.insert(3, -3).is_ok());
+            assert_eq!(sampled_cache.query(&3), Ok(&-3));
+
+            assert_eq!(sampled_cache.remove(&3), Ok(-3));
+            assert_eq!(sampled_cache.remove(&3), Err(CacheError::CacheMiss));
+            assert_eq!(sampled_cache.len(), 3);
+
+            // few samples still keep the cache at capacity
+            let mut sampled_cache = SampledLruCache::<i32, i32>::with_parameters(capacity, 1, 1);
+            for ele in 0..100 {
+                assert!(sampled_cache.insert(ele, ele).is_ok());
+                assert_eq!(sampled_cache.query(&ele), Ok(&ele));
+            }
+            assert_eq!(sampled_cache.len(), capacity);
+        }
+
+        #[test]
+        fn sampled_lru_cache_zero_capacity() {
+            let mut sampled_cache = SampledLruCache::<i32, i32>::with_capacity(0);
+            assert_eq!(sampled_cache.query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(sampled_cache.remove(&0), Err(CacheError::CacheMiss));
+            assert_eq!(
+                sampled_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+            assert!(sampled_cache.is_empty());
+            assert!(sampled_cache.pool.is_empty());
+
+            // zero samples and pool size are raised to 1
+            let mut sampled_cache = SampledLruCache::<i32, i32>::with_parameters(1, 0, 0);
+            assert!(sampled_cache.insert(0, 0).is_ok());
+            assert!(sampled_cache.insert(1, 1).is_ok());
+            assert_eq!(sampled_cache.query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(sampled_cache.query(&1), Ok(&1));
+        }
+
+        #[test]
+        fn sampled_lru_cache_repeated_keys() {
+            let capacity = 2;
+            let mut sampled_cache = SampledLruCache::<i32, i32>::with_parameters(capacity, 64, 2);
+            for ele in 0..3 {
+                assert!(sampled_cache.insert(0, ele).is_ok());
+            }
+            assert_eq!(sampled_cache.len(), 1);
+            assert_eq!(sampled_cache.query(&0), Ok(&2));
+
+            // a repeated insert into a full cache evicts nothing, and stamps the entry
+            assert!(sampled_cache.insert(1, 1).is_ok());
+            assert!(sampled_cache.insert(0, 3).is_ok());
+            assert_eq!(sampled_cache.len(), capacity);
+
+            assert!(sampled_cache.insert(2, 2).is_ok());
+            assert_eq!(sampled_cache.query(&1), Err(CacheError::CacheMiss));
+            assert_eq!(sampled_cache.query(&0), Ok(&3));
+
+            // a pooled candidate removed and inserted again is not mistaken for the old one
+            let index_0 = sampled_cache.block_refs[&0];
+            assert_eq!(sampled_cache.pool, vec![(sampled_cache.clock - 2, index_0)]);
+            assert_eq!(sampled_cache.remove(&0), Ok(3));
+            assert!(sampled_cache.insert(0, 4).is_ok());
+            assert!(sampled_cache.insert(3, 3).is_ok());
+            assert_eq!(sampled_cache.query(&2), Err(CacheError::CacheMiss));
+            assert_eq!(sampled_cache.query(&0), Ok(&4));
+        }
+    }
+}
+
+pub mod adaptive {
+    //! Module providing an adaptive cache, which switches its eviction policy between
+    //! LRU, LFU and ARC to whichever scores the best hit ratio on the workload.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::adaptive::{AdaptiveCache, Policy};
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! // start out with LRU, and review the policy every 100 queries
+    //! let mut adaptive_cache = AdaptiveCache::<i32, i32>::with_parameters(4, Policy::Lru, 100);
+    //!
+    //! // a few frequently used keys, interleaved with scans
+    //! let mut scan = 100;
+    //! for _ in 0..50 {
+    //!     for ele in [0, 1, 0, 1].into_iter().chain(scan..scan + 4) {
+    //!         if adaptive_cache.query(&ele) == Err(CacheError::CacheMiss) {
+    //!             adaptive_cache.insert(ele, ele).unwrap();
+    //!         }
+    //!     }
+    //!     scan += 4;
+    //! }
+    //!
+    //! // the scans flush the frequently used keys out of an LRU cache
+    //! assert_ne!(adaptive_cache.policy(), Policy::Lru);
+    //! assert!(adaptive_cache.decisions().any(|decision| decision.switched));
+    //!
+    //! assert_eq!(adaptive_cache.query(&0), Ok(&0));
+    //! assert_eq!(adaptive_cache.remove(&0), Ok(0));
+    //! assert_eq!(adaptive_cache.remove(&0), Err(CacheError::CacheMiss));
+    //! ```
+    //!
+    //! Memory overhead: besides the values, the cache tracks every resident key in the
+    //! live policy and in each of the three shadows. The ARC shadow also remembers up to
+    //! `capacity` evicted keys in its ghost lists, which grow on demand, so an
+    //! AdaptiveCache holds up to about 5 * capacity keys, plus up to capacity more
+    //! while ARC is the live policy.
+
+    use crate::list::{Link, LinkedList, ListError};
+    use crate::lrucache::CacheError;
+    use std::{
+        collections::{BTreeMap, HashMap, VecDeque},
+        hash::Hash,
+    };
+
+    /// Default number of queries between reviews of the eviction policy.
+    pub const DEFAULT_WINDOW: u64 = 1000;
+
+    /// Number of policy decisions retained in the decision history.
+    pub const DECISION_HISTORY: usize = 64;
+
+    /// Eviction policies an AdaptiveCache chooses from.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Policy {
+        /// Least Recently Used.
+        Lru,
+        /// Least Frequently Used, ties broken by recency.
+        Lfu,
+        /// Adaptive Replacement Cache.
+        Arc,
+    }
+
+    const POLICIES: [Policy; 3] = [Policy::Lru, Policy::Lfu, Policy::Arc];
+
+    /// A review of the eviction policy at the end of a window of queries.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct PolicyDecision {
+        /// Number of queries made to the cache at the time of the review.
+        pub queries: u64,
+        /// Hit ratio scored by the shadow of each policy over the window.
+        pub hit_ratios: Vec<(Policy, f64)>,
+        /// Policy in use after the review.
+        pub policy: Policy,
+        /// Whether the review changed the policy in use.
+        pub switched: bool,
+    }
+
+    /// Key-only LRU policy.
+    struct LruKeys<K> {
+        keys: LinkedList<K>,
+        key_refs: HashMap<K, Link>,
+    }
+
+    impl<K> LruKeys<K>
+    where
+        K: Eq + Hash + Copy,
+    {
+        fn with_capacity(capacity: usize) -> Self {
+            LruKeys {
+                keys: LinkedList::with_capacity(capacity),
+                key_refs: HashMap::new(),
+            }
+        }
+
+        fn access(&mut self, key: &K) -> Result<(), ListError> {
+            match self.key_refs.get(key) {
+                Some(link) => self.keys.reposition_to_tail(link),
+                None => Ok(()),
+            }
+        }
+
+        fn insert(&mut self, key: K) -> Result<Option<K>, ListError> {
+            let evicted = if self.keys.is_full() {
+                let evicted = self.keys.pop_front()?;
+                self.key_refs.remove(&evicted);
+                Some(evicted)
+            } else {
+                None
+            };
+
+            let link = self.keys.push_back(key)?;
+            self.key_refs.insert(key, link);
+
+            Ok(evicted)
+        }
+
+        fn remove(&mut self, key: &K) -> Result<(), ListError> {
+            match self.key_refs.remove(key) {
+                Some(link) => self.keys.remove(&link).map(|_| ()),
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// Key-only LFU policy, with ties in frequency broken by recency.
+    struct LfuKeys<K> {
+        counts: HashMap<K, (u64, u64)>,
+        order: BTreeMap<(u64, u64), K>,
+        capacity: usize,
+        tick: u64,
+    }
+
+    impl<K> LfuKeys<K>
+    where
+        K: Eq + Hash + Copy,
+    {
+        fn with_capacity(capacity: usize) -> Self {
+            LfuKeys {
+                counts: HashMap::new(),
+                order: BTreeMap::new(),
+                capacity,
+                tick: 0,
+            }
+        }
+
+        fn access(&mut self, key: &K) -> Result<(), ListError> {
+            if let Some(count) = self.counts.get_mut(key) {
+                self.order.remove(count);
+                self.tick += 1;
+                *count = (count.0 + 1, self.tick);
+                self.order.insert(*count, *key);
+            }
+
+            Ok(())
+        }
+
+        fn insert(&mut self, key: K) -> Result<Option<K>, ListError> {
+            let evicted = if self.counts.len() >= self.capacity {
+                let (_, evicted) = self.order.pop_first().ok_or(ListError::ListEmpty)?;
+                self.counts.remove(&evicted);
+                Some(evicted)
+            } else {
+                None
+            };
+
+            self.tick += 1;
+            self.counts.insert(key, (1, self.tick));
+            self.order.insert((1, self.tick), key);
+
+            Ok(evicted)
+        }
+
+        fn remove(&mut self, key: &K) -> Result<(), ListError> {
+            if let Some(count) = self.counts.remove(key) {
+                self.order.remove(&count);
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Lists making up an ARC policy: T1 and T2 hold resident keys seen once and more
+    /// than once recently, while B1 and B2 hold the keys evicted from them.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum ArcList {
+        T1,
+        T2,
+        B1,
+        B2,
+    }
+
+    /// Key-only ARC policy, following "ARC: A Self-Tuning, Low Overhead Replacement Cache"
+    /// by Nimrod Megiddo and Dharmendra S. Modha.
+    struct ArcKeys<K> {
+        t1: LinkedList<K>,
+        t2: LinkedList<K>,
+        b1: LinkedList<K>,
+        b2: LinkedList<K>,
+        key_refs: HashMap<K, (ArcList, Link)>,
+
+        capacity: usize,
+        target_t1: usize,
+    }
+
+    impl<K> ArcKeys<K>
+    where
+        K: Eq + Hash + Copy,
+    {
+        fn with_capacity(capacity: usize) -> Self {
+            // the lists share a budget of 2 * capacity keys, so they grow on demand
+            // instead of each reserving room for the worst case
+            ArcKeys {
+                t1: LinkedList::new(),
+                t2: LinkedList::new(),
+                b1: LinkedList::new(),
+                b2: LinkedList::new(),
+                key_refs: HashMap::new(),
+                capacity,
+                target_t1: 0,
+            }
+        }
+
+        fn list_mut(&mut self, list: ArcList) -> &mut LinkedList<K> {
+            match list {
+                ArcList::T1 => &mut self.t1,
+                ArcList::T2 => &mut self.t2,
+                ArcList::B1 => &mut self.b1,
+                ArcList::B2 => &mut self.b2,
+            }
+        }
+
+        fn push(&mut self, list: ArcList, key: K) -> Result<(), ListError> {
+            let keys = self.list_mut(list);
+            if keys.is_full() {
+                keys.reserve(keys.len().max(1));
+            }
+
+            let link = keys.push_back(key)?;
+            self.key_refs.insert(key, (list, link));
+            Ok(())
+        }
+
+        /// Moves the least recently used key out of the given list, into the given list
+        /// if any. Returns the key moved.
+        fn demote(&mut self, from: ArcList, to: Option<ArcList>) -> Result<K, ListError> {
+            let key = self.list_mut(from).pop_front()?;
+            self.key_refs.remove(&key);
+
+            if let Some(to) = to {
+                self.push(to, key)?;
+            }
+
+            Ok(key)
+        }
+
+        fn access(&mut self, key: &K) -> Result<(), ListError> {
+            if let Some((list @ (ArcList::T1 | ArcList::T2), link)) =
+                self.key_refs.get(key).copied()
+            {
+                self.list_mut(list).remove(&link)?;
+                self.push(ArcList::T2, *key)?;
+            }
+
+            Ok(())
+        }
+
+        /// Evicts a resident key into its ghost list, from T1 if it holds more keys than
+        /// its target size, and from T2 otherwise.
+        fn replace(&mut self, in_b2: bool) -> Result<Option<K>, ListError> {
+            if self.t1.len() + self.t2.len() < self.capacity {
+                return Ok(None);
+            }
+
+            let t1_len = self.t1.len();
+            let from_t1 = t1_len >= 1
+                && ((in_b2 && t1_len == self.target_t1)
+                    || t1_len > self.target_t1
+                    || self.t2.is_empty());
+
+            if from_t1 {
+                self.demote(ArcList::T1, Some(ArcList::B1)).map(Some)
+            } else {
+                self.demote(ArcList::T2, Some(ArcList::B2)).map(Some)
+            }
+        }
+
+        fn insert(&mut self, key: K) -> Result<Option<K>, ListError> {
+            if self.capacity == 0 {
+                return Err(ListError::ListEmpty);
+            }
+
+            match self.key_refs.get(&key).copied() {
+                Some((ArcList::B1, link)) => {
+                    let delta = (self.b2.len() / self.b1.len()).max(1);
+                    self.target_t1 = (self.target_t1 + delta).min(self.capacity);
+
+                    self.b1.remove(&link)?;
+                    self.key_refs.remove(&key);
+                    let evicted = self.replace(false)?;
+                    self.push(ArcList::T2, key)?;
+                    Ok(evicted)
+                }
+                Some((ArcList::B2, link)) => {
+                    let delta = (self.b1.len() / self.b2.len()).max(1);
+                    self.target_t1 = self.target_t1.saturating_sub(delta);
+
+                    self.b2.remove(&link)?;
+                    self.key_refs.remove(&key);
+                    let evicted = self.replace(true)?;
+                    self.push(ArcList::T2, key)?;
+                    Ok(evicted)
+                }
+                Some(_) => Ok(None),
+                None => {
+                    let l1_len = self.t1.len() + self.b1.len();
+                    let total_len = l1_len + self.t2.len() + self.b2.len();
+
+                    let evicted = if l1_len >= self.capacity {
+                        if self.t1.len() < self.capacity {
+                            self.demote(ArcList::B1, None)?;
+                            self.replace(false)?
+                        } else {
+                            Some(self.demote(ArcList::T1, None)?)
+                        }
+                    } else if total_len >= self.capacity {
+                        if total_len >= 2 * self.capacity {
+                            self.demote(ArcList::B2, None)?;
+                        }
+                        self.replace(false)?
+                    } else {
+                        None
+                    };
+
+                    self.push(ArcList::T1, key)?;
+                    Ok(evicted)
+                }
+            }
+        }
+
+        fn remove(&mut self, key: &K) -> Result<(), ListError> {
+            if let Some((list, link)) = self.key_refs.remove(key) {
+                self.list_mut(list).remove(&link)?;
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Key-only implementation of one of the policies.
+    enum PolicyKeys<K> {
+        Lru(LruKeys<K>),
+        Lfu(LfuKeys<K>),
+        Arc(Box<ArcKeys<K>>),
+    }
+
+    impl<K> PolicyKeys<K>
+    where
+        K: Eq + Hash + Copy,
+    {
+        fn with_capacity(policy: Policy, capacity: usize) -> Self {
+            match policy {
+                Policy::Lru => PolicyKeys::Lru(LruKeys::with_capacity(capacity)),
+                Policy::Lfu => PolicyKeys::Lfu(LfuKeys::with_capacity(capacity)),
+                Policy::Arc => PolicyKeys::Arc(Box::new(ArcKeys::with_capacity(capacity))),
+            }
+        }
+
+        /// Returns whether the given key is resident as per this policy.
+        fn contains(&self, key: &K) -> bool {
+            match self {
+                PolicyKeys::Lru(keys) => keys.key_refs.contains_key(key),
+                PolicyKeys::Lfu(keys) => keys.counts.contains_key(key),
+                PolicyKeys::Arc(keys) => {
+                    matches!(keys.key_refs.get(key), Some((ArcList::T1 | ArcList::T2, _)))
+                }
+            }
+        }
+
+        /// Records a hit on the given resident key.
+        fn access(&mut self, key: &K) -> Result<(), ListError> {
+            match self {
+                PolicyKeys::Lru(keys) => keys.access(key),
+                PolicyKeys::Lfu(keys) => keys.access(key),
+                PolicyKeys::Arc(keys) => keys.access(key),
+            }
+        }
+
+        /// Admits the given non-resident key, returning the resident key evicted to make
+        /// room for it, if any.
+        fn insert(&mut self, key: K) -> Result<Option<K>, ListError> {
+            match self {
+                PolicyKeys::Lru(keys) => keys.insert(key),
+                PolicyKeys::Lfu(keys) => keys.insert(key),
+                PolicyKeys::Arc(keys) => keys.insert(key),
+            }
+        }
+
+        fn remove(&mut self, key: &K) -> Result<(), ListError> {
+            match self {
+                PolicyKeys::Lru(keys) => keys.remove(key),
+                PolicyKeys::Lfu(keys) => keys.remove(key),
+                PolicyKeys::Arc(keys) => keys.remove(key),
+            }
+        }
+    }
+
+    /// Shadow instance of a policy, scoring its hits over the current window.
+    struct Shadow<K> {
+        policy: Policy,
+        keys: PolicyKeys<K>,
+        hits: u64,
+    }
+
+    /// An adaptive cache, storing values in a hash map and evicting them as per the live
+    /// policy. Alongside, key-only shadow instances of every policy are run over the
+    /// queries made to the cache, scoring their hit ratio. At the end of every window of
+    /// queries, the live policy is switched to the best scoring shadow policy, ties
+    /// going to the live policy.
+    ///
+    /// On a switch, the new live policy is seeded with the resident keys in order of
+    /// their last access, so that access frequencies start over.
+    pub struct AdaptiveCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        values: HashMap<K, (V, u64)>,
+        live: PolicyKeys<K>,
+        policy: Policy,
+        shadows: Vec<Shadow<K>>,
+
+        capacity: usize,
+        window: u64,
+        queries: u64,
+        tick: u64,
+
+        decisions: VecDeque<PolicyDecision>,
+    }
+
+    impl<K, V> AdaptiveCache<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        /// Creates an AdaptiveCache instance with the given capacity, starting out with
+        /// the LRU policy and reviewing it every `DEFAULT_WINDOW` queries. A zero capacity
+        /// AdaptiveCache is unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            Self::with_parameters(capacity, Policy::Lru, DEFAULT_WINDOW)
+        }
+
+        /// Creates an AdaptiveCache instance with the given capacity, initial policy, and
+        /// number of queries (at least 1) between reviews of the policy.
+        pub fn with_parameters(capacity: usize, policy: Policy, window: u64) -> Self {
+            AdaptiveCache {
+                values: HashMap::new(),
+                live: PolicyKeys::with_capacity(policy, capacity),
+                policy,
+                shadows: POLICIES
+                    .iter()
+                    .map(|&policy| Shadow {
+                        policy,
+                        keys: PolicyKeys::with_capacity(policy, capacity),
+                        hits: 0,
+                    })
+                    .collect(),
+                capacity,
+                window: window.max(1),
+                queries: 0,
+                tick: 0,
+                decisions: VecDeque::with_capacity(DECISION_HISTORY),
+            }
+        }
+
+        /// Returns the live eviction policy.
+        pub fn policy(&self) -> Policy {
+            self.policy
+        }
+
+        /// Returns the most recent policy decisions, oldest first.
+        pub fn decisions(&self) -> impl Iterator<Item = &PolicyDecision> {
+            self.decisions.iter()
+        }
+
+        /// Returns a reference to the value associated with the given key. If the key is
+        /// not present in the cache, we return a "cache-miss" error. Every query is
+        /// replayed on the shadow policies, and may trigger a review of the live policy.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            self.record_query(key)?;
+
+            if !self.values.contains_key(key) {
+                return Err(CacheError::CacheMiss);
+            }
+
+            self.live.access(key).map_err(CacheError::CacheBroken)?;
+            self.tick += 1;
+
+            let (value, tick) = self
+                .values
+                .get_mut(key)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))?;
+            *tick = self.tick;
+
+            Ok(value)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error. The key is forgotten by the
+        /// shadow policies too, including the ARC ghost lists, so a later query of it
+        /// scores no hit. Returns the value associated, after removal with ownership.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            let (value, _) = self.values.remove(key).ok_or(CacheError::CacheMiss)?;
+            self.live.remove(key).map_err(CacheError::CacheBroken)?;
+
+            for shadow in self.shadows.iter_mut() {
+                shadow.keys.remove(key).map_err(CacheError::CacheBroken)?;
+            }
+
+            Ok(value)
+        }
+
+        /// Inserts a new key value pair into this cache. If this cache is full, the entry
+        /// chosen by the live policy is removed. Keys unknown to a shadow policy are
+        /// admitted to it, without counting towards its score.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            for shadow in self.shadows.iter_mut() {
+                if !shadow.keys.contains(&key) {
+                    shadow.keys.insert(key).map_err(CacheError::CacheBroken)?;
+                }
+            }
+
+            self.tick += 1;
+
+            if let Some(entry) = self.values.get_mut(&key) {
+                *entry = (value, self.tick);
+                return self.live.access(&key).map_err(CacheError::CacheBroken);
+            }
+
+            if let Some(evicted) = self.live.insert(key).map_err(CacheError::CacheBroken)? {
+                self.values.remove(&evicted);
+            }
+            self.values.insert(key, (value, self.tick));
+
+            Ok(())
+        }
+
+        fn record_query(&mut self, key: &K) -> Result<(), CacheError> {
+            for shadow in self.shadows.iter_mut() {
+                if shadow.keys.contains(key) {
+                    shadow.hits += 1;
+                    shadow.keys.access(key)
+                } else {
+                    shadow.keys.insert(*key).map(|_| ())
+                }
+                .map_err(CacheError::CacheBroken)?;
+            }
+
+            self.queries += 1;
+            if self.queries % self.window == 0 {
+                self.review_policy()?;
+            }
+
+            Ok(())
+        }
+
+        /// Switches the live policy to the best scoring shadow policy over the last
+        /// window, and starts a new window.
+        fn review_policy(&mut self) -> Result<(), CacheError> {
+            let hit_ratios: Vec<(Policy, f64)> = self
+                .shadows
+                .iter()
+                .map(|shadow| (shadow.policy, shadow.hits as f64 / self.window as f64))
+                .collect();
+
+            let live_ratio = hit_ratios
+                .iter()
+                .find(|(policy, _)| policy == &self.policy)
+                .map_or(0.0, |(_, ratio)| *ratio);
+            let (best, _) =
+                hit_ratios
+                    .iter()
+                    .fold((self.policy, live_ratio), |best, &(policy, ratio)| {
+                        if ratio > best.1 {
+                            (policy, ratio)
+                        } else {
+                            best
+                        }
+                    });
+
+            let switched = best != self.policy;
+            if switched {
+                self.switch_policy(best)?;
+            }
+
+            for shadow in self.shadows.iter_mut() {
+                shadow.hits = 0;
+            }
+
+            if self.decisions.len() == DECISION_HISTORY {
+                self.decisions.pop_front();
+            }
+            self.decisions.push_back(PolicyDecision {
+                queries: self.queries,
+                hit_ratios,
+                policy: self.policy,
+                switched,
+            });
+
+            Ok(())
+        }
+
+        fn switch_policy(&mut self, policy: Policy) -> Result<(), CacheError> {
+            let mut resident: Vec<(u64, K)> = self
+                .values
+                .iter()
+                .map(|(key, (_, tick))| (*tick, *key))
+                .collect();
+            resident.sort_unstable_by_key(|(tick, _)| *tick);
+
+            let mut live = PolicyKeys::with_capacity(policy, self.capacity);
+            for (_, key) in resident {
+                live.insert(key).map_err(CacheError::CacheBroken)?;
+            }
+
+            self.live = live;
+            self.policy = policy;
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn hit_count(keys: &mut PolicyKeys<i32>, trace: &[i32]) -> usize {
+            let mut hits = 0;
+            for key in trace {
+                if keys.contains(key) {
+                    keys.access(key).unwrap();
+                    hits += 1;
+                } else {
+                    keys.insert(*key).unwrap();
+                }
+            }
+            hits
+        }
+
+        #[test]
+        fn policy_keys_consistency() {
+            for policy in POLICIES {
+                let mut keys = PolicyKeys::with_capacity(policy, 0);
+                assert_eq!(keys.insert(0), Err(ListError::ListEmpty));
+
+                let mut keys = PolicyKeys::with_capacity(policy, 2);
+                assert_eq!(keys.insert(0), Ok(None));
+                assert_eq!(keys.insert(1), Ok(None));
+                assert!(keys.contains(&0) && keys.contains(&1));
+
+                keys.remove(&1).unwrap();
+                assert!(!keys.contains(&1));
+                assert_eq!(keys.insert(1), Ok(None));
+            }
+
+            // LRU evicts 0, while LFU keeps it for its frequency
+            let trace = [0, 0, 0, 1, 2, 0];
+            let mut lru_keys = PolicyKeys::with_capacity(Policy::Lru, 2);
+            let mut lfu_keys = PolicyKeys::with_capacity(Policy::Lfu, 2);
+            assert_eq!(hit_count(&mut lru_keys, &trace), 2);
+            assert_eq!(hit_count(&mut lfu_keys, &trace), 3);
+
+            // ARC keeps 0 in T2 for being seen twice, and tracks 1 in B1 after eviction
+            let mut arc_keys = ArcKeys::with_capacity(2);
+            for key in [0, 0, 1, 2] {
+                if arc_keys.insert(key) == Ok(None) && key == 0 {
+                    arc_keys.access(&0).unwrap();
+                }
+            }
+            assert!(arc_keys.t2.iter().eq([0].iter()));
+            assert!(arc_keys.t1.iter().eq([2].iter()));
+            assert!(arc_keys.b1.iter().eq([1].iter()));
+
+            // a hit in B1 grows the target size of T1, which T1 is now at
+            assert_eq!(arc_keys.insert(1), Ok(Some(0)));
+            assert_eq!(arc_keys.target_t1, 1);
+            assert!(arc_keys.t1.iter().eq([2].iter()));
+            assert!(arc_keys.t2.iter().eq([1].iter()));
+            assert!(arc_keys.b2.iter().eq([0].iter()));
+        }
+
+        #[test]
+        fn arc_keys_lazy_lists() {
+            let mut arc_keys = ArcKeys::<i32>::with_capacity(64);
+            assert_eq!(arc_keys.t1.capacity() + arc_keys.b2.capacity(), 0);
+
+            assert_eq!(arc_keys.insert(0), Ok(None));
+            assert_eq!(arc_keys.t1.capacity(), 1);
+            assert_eq!(arc_keys.t2.capacity() + arc_keys.b1.capacity(), 0);
+
+            // a scan stays in T1 and B1, so T2 and B2 never allocate
+            for key in 1..256 {
+                arc_keys.insert(key).unwrap();
+            }
+            assert_eq!(arc_keys.t1.len() + arc_keys.b1.len(), 64);
+            assert!(arc_keys.t1.capacity() <= 128);
+            assert_eq!(arc_keys.t2.capacity() + arc_keys.b2.capacity(), 0);
+        }
+
+        #[test]
+        fn adaptive_cache_consistency() {
+            let mut adaptive_cache = AdaptiveCache::<i32, i32>::with_capacity(0);
+            assert_eq!(
+                adaptive_cache.insert(0, 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+
+            let mut adaptive_cache = AdaptiveCache::<i32, i32>::with_parameters(2, Policy::Lru, 4);
+            assert_eq!(adaptive_cache.query(&0), Err(CacheError::CacheMiss));
+            assert!(adaptive_cache.insert(0, 0).is_ok());
+            assert_eq!(adaptive_cache.query(&0), Ok(&0));
+            assert_eq!(adaptive_cache.query(&0), Ok(&0));
+            assert_eq!(adaptive_cache.query(&1), Err(CacheError::CacheMiss));
+            assert!(adaptive_cache.insert(1, 1).is_ok());
+
+            // all shadows scored the same over the first window
+            let decision = adaptive_cache.decisions().last().unwrap();
+            assert_eq!(decision.queries, 4);
+            assert!(decision.hit_ratios.iter().all(|(_, ratio)| ratio == &0.5));
+            assert_eq!(decision.policy, Policy::Lru);
+            assert!(!decision.switched);
+
+            // LRU evicts 0 twice, while LFU and ARC keep it around
+            for key in [2, 0, 3] {
+                assert_eq!(adaptive_cache.query(&key), Err(CacheError::CacheMiss));
+                assert!(adaptive_cache.insert(key, key).is_ok());
+            }
+            assert_eq!(adaptive_cache.query(&0), Ok(&0));
+
+            let decision = adaptive_cache.decisions().last().unwrap();
+            assert_eq!(decision.queries, 8);
+            assert_eq!(
+                decision.hit_ratios,
+                vec![(Policy::Lru, 0.25), (Policy::Lfu, 0.5), (Policy::Arc, 0.5)]
+            );
+            assert_eq!(decision.policy, Policy::Lfu);
+            assert!(decision.switched);
+            assert_eq!(adaptive_cache.policy(), Policy::Lfu);
+            assert_eq!(adaptive_cache.decisions().count(), 2);
+
+            // the live LFU policy was seeded with the resident keys, and 0 has been
+            // used since
+            assert!(adaptive_cache.live.contains(&0));
+            assert!(adaptive_cache.live.contains(&3));
+            assert!(adaptive_cache.insert(4, 4).is_ok());
+            assert!(!adaptive_cache.values.contains_key(&3));
+            assert_eq!(adaptive_cache.values.len(), 2);
+
+            assert!(adaptive_cache.insert(4, -4).is_ok());
+            assert_eq!(adaptive_cache.query(&4), Ok(&-4));
+            assert_eq!(adaptive_cache.remove(&4), Ok(-4));
+            assert_eq!(adaptive_cache.remove(&4), Err(CacheError::CacheMiss));
+            assert!(!adaptive_cache.live.contains(&4));
+            assert!(adaptive_cache
+                .shadows
+                .iter()
+                .all(|shadow| !shadow.keys.contains(&4)));
+
+            // a key live in the cache but in a ghost list of the ARC shadow is forgotten too
+            let mut adaptive_cache = AdaptiveCache::<i32, i32>::with_parameters(2, Policy::Lru, 8);
+            assert!(adaptive_cache.insert(0, 0).is_ok());
+            assert!(adaptive_cache.insert(1, 1).is_ok());
+            assert_eq!(adaptive_cache.query(&1), Ok(&1));
+            for shadow in adaptive_cache.shadows.iter_mut() {
+                if let PolicyKeys::Arc(keys) = &mut shadow.keys {
+                    assert_eq!(keys.insert(2), Ok(Some(0)));
+                    assert!(keys.b1.iter().eq([0].iter()));
+                }
+            }
+
+            assert_eq!(adaptive_cache.remove(&0), Ok(0));
+            for shadow in adaptive_cache.shadows.iter() {
+                if let PolicyKeys::Arc(keys) = &shadow.keys {
+                    assert!(keys.b1.iter().next().is_none());
+                    assert!(!keys.key_refs.contains_key(&0));
+                }
+            }
+        }
+    }
+}
+
+pub mod priority {
+    //! Module providing a Least-Recently-Used Cache with priority classes. Every class
+    //! keeps its own recency list, with all lists sharing one generational arena. Entries
+    //! are evicted from the lowest non-empty class first.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::priority::{PriorityCache, PriorityError};
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! const PREFETCH: usize = 0;
+    //! const DEMAND: usize = 1;
+    //!
+    //! let mut priority_cache = PriorityCache::<i32, i32>::with_capacity(3, 2);
+    //!
+    //! assert!(priority_cache.insert(0, 0, DEMAND).is_ok());
+    //! assert!(priority_cache.insert(1, 1, PREFETCH).is_ok());
+    //! assert!(priority_cache.insert(2, 2, PREFETCH).is_ok());
+    //! assert_eq!(priority_cache.query(&1), Ok(&1));
+    //!
+    //! // 0 is least recently used, but prefetched entries go first
+    //! assert!(priority_cache.insert(3, 3, DEMAND).is_ok());
+    //! assert_eq!(priority_cache.query(&2), Err(CacheError::CacheMiss));
+    //!
+    //! // a prefetched entry turning out useful can be promoted
+    //! assert!(priority_cache.set_class(&1, DEMAND).is_ok());
+    //! assert!(priority_cache.insert(4, 4, PREFETCH).is_ok());
+    //! assert!(priority_cache.insert(5, 5, DEMAND).is_ok());
+    //! assert_eq!(priority_cache.query(&4), Err(CacheError::CacheMiss));
+    //! assert_eq!(priority_cache.query(&0), Err(CacheError::CacheMiss));
+    //! assert_eq!(priority_cache.class_of(&1), Some(DEMAND));
+    //!
+    //! assert_eq!(
+    //!     priority_cache.insert(6, 6, 2),
+    //!     Err(PriorityError::NoSuchClass)
+    //! );
+    //! ```
+
+    use crate::arena::Arena;
+    use crate::list::{Link, ListError, Node, SharedList};
+    use crate::lrucache::CacheError;
+    use std::{collections::HashMap, fmt::Display, hash::Hash};
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum PriorityError {
+        Cache(CacheError),
+        NoSuchClass,
+    }
+
+    impl Display for PriorityError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match &self {
+                PriorityError::Cache(cache_error) => cache_error.fmt(f),
+                PriorityError::NoSuchClass => write!(f, "Priority class does not exist."),
+            }
+        }
+    }
+
+    impl From<CacheError> for PriorityError {
+        fn from(cache_error: CacheError) -> Self {
+            PriorityError::Cache(cache_error)
+        }
+    }
+
+    /// Cache block storing some key and value, along with its priority class.
+    pub struct PriorityBlock<K, V> {
+        pub key: K,
+        pub value: V,
+        pub class: usize,
+    }
+
+    /// A Least-Recently-Used Cache with priority classes, numbered upwards from 0 as
+    /// the lowest priority. The recency list of every class has its least recently used
+    /// entry at the head.
+    pub struct PriorityCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        blocks: Arena<Node<PriorityBlock<K, V>>>,
+        block_refs: HashMap<K, Link>,
+        classes: Vec<SharedList>,
+        len: usize,
+    }
+
+    impl<K, V> PriorityCache<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        /// Creates a PriorityCache with the given capacity, shared by the given number of
+        /// priority classes. A zero capacity PriorityCache is unusable.
+        pub fn with_capacity(capacity: usize, classes: usize) -> Self {
+            PriorityCache {
+                blocks: Arena::with_capacity(capacity),
+                block_refs: HashMap::new(),
+                classes: (0..classes).map(|_| SharedList::new()).collect(),
+                len: 0,
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        pub fn classes(&self) -> usize {
+            self.classes.len()
+        }
+
+        /// Returns the number of entries in the given priority class.
+        pub fn class_len(&self, class: usize) -> usize {
+            self.classes.get(class).map_or(0, SharedList::len)
+        }
+
+        /// Returns the priority class of the entry for the given key, if any.
+        pub fn class_of(&self, key: &K) -> Option<usize> {
+            let link = self.block_refs.get(key)?;
+            self.blocks.get(&link.index).map(|node| node.value.class)
+        }
+
+        fn block(&self, link: &Link) -> Result<&PriorityBlock<K, V>, CacheError> {
+            self.blocks
+                .get(&link.index)
+                .map(|node| &node.value)
+                .ok_or(CacheError::CacheBroken(ListError::LinkBroken))
+        }
+
+        fn remove_block(&mut self, link: &Link) -> Result<PriorityBlock<K, V>, CacheError> {
+            let class = self.block(link)?.class;
+            let block = self.classes[class]
+                .remove(&mut self.blocks, link)
+                .map_err(CacheError::CacheBroken)?;
+            self.block_refs.remove(&block.key);
+            self.len -= 1;
+            Ok(block)
+        }
+
+        /// Adds the given block as the most recently used entry of its class.
+        fn push_block(&mut self, block: PriorityBlock<K, V>) -> Result<(), CacheError> {
+            let key = block.key;
+            let link = self.classes[block.class]
+                .push_back(&mut self.blocks, block)
+                .map_err(CacheError::CacheBroken)?;
+            self.block_refs.insert(key, link);
+            self.len += 1;
+            Ok(())
+        }
+
+        /// Evicts the least recently used entry of the lowest non-empty priority class.
+        fn evict(&mut self) -> Result<(), CacheError> {
+            let victim = self
+                .classes
+                .iter()
+                .find_map(SharedList::head)
+                .ok_or(CacheError::CacheBroken(ListError::ListEmpty))?;
+            self.remove_block(&victim)?;
+            Ok(())
+        }
+
+        /// Returns a reference to the value associated with the given key, marking it as
+        /// the most recently used entry of its class. If the key is not present in the
+        /// cache, we return a "cache-miss" error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let link = *self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            let class = self.block(&link)?.class;
+            self.classes[class]
+                .reposition_to_tail(&mut self.blocks, &link)
+                .map_err(CacheError::CacheBroken)?;
+            Ok(&self.block(&link)?.value)
+        }
+
+        /// Moves the entry for the given key to the given priority class, as its most
+        /// recently used entry. If no entry is found, we return a "cache-miss" error.
+        pub fn set_class(&mut self, key: &K, class: usize) -> Result<(), PriorityError> {
+            if class >= self.classes.len() {
+                return Err(PriorityError::NoSuchClass);
+            }
+
+            let link = *self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            let mut block = self.remove_block(&link)?;
+            block.class = class;
+            Ok(self.push_block(block)?)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            let link = *self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            self.remove_block(&link).map(|block| block.value)
+        }
+
+        /// Inserts a new key value pair into the given priority class of this cache. An
+        /// existing entry for the key is updated and moved to the given class. If this
+        /// cache is full, the least recently used entry of the lowest non-empty class is
+        /// removed.
+        pub fn insert(&mut self, key: K, value: V, class: usize) -> Result<(), PriorityError> {
+            if class >= self.classes.len() {
+                return Err(PriorityError::NoSuchClass);
+            }
+
+            if let Some(link) = self.block_refs.get(&key).copied() {
+                self.remove_block(&link)?;
+            } else if self.len == self.blocks.capacity() {
+                self.evict()?;
+            }
+
+            Ok(self.push_block(PriorityBlock { key, value, class })?)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn class_keys<K: Eq + Hash + Copy, V>(cache: &PriorityCache<K, V>, class: usize) -> Vec<K> {
+            cache.classes[class]
+                .iter(&cache.blocks)
+                .map(|block| block.key)
+                .collect()
+        }
+
+        #[test]
+        fn priority_cache_consistency() {
+            let mut priority_cache = PriorityCache::<i32, i32>::with_capacity(0, 1);
+            assert_eq!(
+                priority_cache.insert(0, 0, 0),
+                Err(PriorityError::Cache(CacheError::CacheBroken(
+                    ListError::ListEmpty
+                )))
+            );
+
+            let mut priority_cache = PriorityCache::<i32, i32>::with_capacity(4, 3);
+            assert_eq!(priority_cache.classes(), 3);
+            assert_eq!(
+                priority_cache.set_class(&0, 0),
+                Err(PriorityError::Cache(CacheError::CacheMiss))
+            );
+            assert!(priority_cache.insert(0, 0, 2).is_ok());
+            assert!(priority_cache.insert(1, 1, 1).is_ok());
+            assert!(priority_cache.insert(2, 2, 0).is_ok());
+            assert!(priority_cache.insert(3, 3, 1).is_ok());
+            assert_eq!(
+                priority_cache.set_class(&0, 3),
+                Err(PriorityError::NoSuchClass)
+            );
+
+            assert_eq!(priority_cache.query(&1), Ok(&1));
+            assert_eq!(class_keys(&priority_cache, 1), [3, 1]);
+
+            // the lowest class is drained first, then recency decides
+            assert!(priority_cache.insert(4, 4, 2).is_ok());
+            assert_eq!(priority_cache.query(&2), Err(CacheError::CacheMiss));
+            assert!(priority_cache.insert(5, 5, 2).is_ok());
+            assert_eq!(priority_cache.query(&3), Err(CacheError::CacheMiss));
+            assert_eq!(priority_cache.class_len(0), 0);
+            assert_eq!(priority_cache.class_len(1), 1);
+            assert_eq!(priority_cache.class_len(2), 3);
+
+            // updates move entries between classes
+            assert!(priority_cache.insert(4, -4, 0).is_ok());
+            assert!(priority_cache.set_class(&1, 2).is_ok());
+            assert_eq!(class_keys(&priority_cache, 0), [4]);
+            assert_eq!(class_keys(&priority_cache, 1), []);
+            assert_eq!(class_keys(&priority_cache, 2), [0, 5, 1]);
+            assert!(priority_cache.insert(6, 6, 1).is_ok());
+            assert_eq!(priority_cache.class_of(&4), None);
+
+            assert_eq!(priority_cache.remove(&5), Ok(5));
+            assert_eq!(priority_cache.remove(&5), Err(CacheError::CacheMiss));
+            assert_eq!(class_keys(&priority_cache, 2), [0, 1]);
+            assert_eq!(priority_cache.len(), 3);
+
+            for ele in 7..10 {
+                assert!(priority_cache.insert(ele, ele, 2).is_ok());
+            }
+            assert_eq!(class_keys(&priority_cache, 1), []);
+            assert_eq!(class_keys(&priority_cache, 2), [1, 7, 8, 9]);
+            assert_eq!(priority_cache.len(), 4);
+        }
+
+        #[test]
+        fn priority_cache_zero_capacity() {
+            let mut priority_cache = PriorityCache::<i32, i32>::with_capacity(0, 1);
+            assert_eq!(priority_cache.query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(priority_cache.remove(&0), Err(CacheError::CacheMiss));
+            assert_eq!(
+                priority_cache.set_class(&0, 0),
+                Err(PriorityError::Cache(CacheError::CacheMiss))
+            );
+            assert_eq!(
+                priority_cache.insert(0, 0, 0),
+                Err(PriorityError::Cache(CacheError::CacheBroken(
+                    ListError::ListEmpty
+                )))
+            );
+            assert!(priority_cache.is_empty());
+
+            // without classes, nothing can be inserted
+            let mut priority_cache = PriorityCache::<i32, i32>::with_capacity(2, 0);
+            assert_eq!(
+                priority_cache.insert(0, 0, 0),
+                Err(PriorityError::NoSuchClass)
+            );
+            assert_eq!(priority_cache.class_len(0), 0);
+            assert!(priority_cache.is_empty());
+        }
+
+        #[test]
+        fn priority_cache_repeated_keys() {
+            let mut priority_cache = PriorityCache::<i32, i32>::with_capacity(2, 2);
+            for ele in 0..3 {
+                assert!(priority_cache.insert(0, ele, 0).is_ok());
+            }
+            assert_eq!(priority_cache.len(), 1);
+            assert_eq!(priority_cache.class_len(0), 1);
+            assert_eq!(priority_cache.query(&0), Ok(&2));
+
+            // a repeated insert into a full cache evicts nothing, and marks the entry used
+            assert!(priority_cache.insert(1, 1, 0).is_ok());
+            assert!(priority_cache.insert(0, 3, 0).is_ok());
+            assert_eq!(class_keys(&priority_cache, 0), [1, 0]);
+            assert_eq!(priority_cache.len(), 2);
+
+            // setting the class an entry already has marks it used as well
+            assert!(priority_cache.set_class(&1, 0).is_ok());
+            assert_eq!(class_keys(&priority_cache, 0), [0, 1]);
+
+            // a repeated key with a missing class leaves the existing entry in place
+            assert_eq!(
+                priority_cache.insert(0, -1, 2),
+                Err(PriorityError::NoSuchClass)
+            );
+            assert_eq!(priority_cache.class_of(&0), Some(0));
+            assert_eq!(priority_cache.query(&0), Ok(&3));
+        }
+    }
+}
+
+pub mod namespaced {
+    //! Module providing a Least-Recently-Used Cache shared by several namespaces, such as
+    //! the tenants of a service. Every namespace may be guaranteed a minimum share of the
+    //! capacity and limited to a maximum share. When the cache is full, entries are evicted
+    //! from the namespace furthest over its guaranteed share.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::namespaced::{NamespaceError, NamespacedCache, NamespaceQuota};
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! let mut namespaced_cache = NamespacedCache::<&str, i32, i32>::with_capacity(4);
+    //! let quota = NamespaceQuota { min: 2, max: None };
+    //! assert!(namespaced_cache.set_quota("quiet", quota).is_ok());
+    //!
+    //! assert!(namespaced_cache.insert("quiet", 0, 0).is_ok());
+    //! assert!(namespaced_cache.insert("quiet", 1, 1).is_ok());
+    //!
+    //! // a noisy namespace only evicts its own entries
+    //! for ele in 0..10 {
+    //!     assert!(namespaced_cache.insert("noisy", ele, ele).is_ok());
+    //! }
+    //! assert_eq!(namespaced_cache.query("quiet", &0), Ok(&0));
+    //! assert_eq!(namespaced_cache.query("quiet", &1), Ok(&1));
+    //! assert_eq!(namespaced_cache.query("noisy", &0), Err(CacheError::CacheMiss));
+    //! assert_eq!(namespaced_cache.namespace_len(&"noisy"), 2);
+    //!
+    //! let stats = namespaced_cache.stats(&"noisy").unwrap();
+    //! assert_eq!((stats.misses, stats.evictions), (1, 8));
+    //!
+    //! // guaranteed shares cannot exceed the capacity
+    //! let quota = NamespaceQuota { min: 3, max: None };
+    //! assert_eq!(
+    //!     namespaced_cache.set_quota("noisy", quota),
+    //!     Err(NamespaceError::QuotaExceeded)
+    //! );
+    //! ```
+
+    use crate::list::{Link, LinkedList, ListError};
+    use crate::lrucache::CacheError;
+    use std::{cmp::Reverse, collections::HashMap, fmt::Display, hash::Hash};
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum NamespaceError {
+        Cache(CacheError),
+        QuotaExceeded,
+    }
+
+    impl Display for NamespaceError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match &self {
+                NamespaceError::Cache(cache_error) => cache_error.fmt(f),
+                NamespaceError::QuotaExceeded => write!(f, "Namespace quota cannot be met."),
+            }
+        }
+    }
+
+    impl From<CacheError> for NamespaceError {
+        fn from(cache_error: CacheError) -> Self {
+            NamespaceError::Cache(cache_error)
+        }
+    }
+
+    /// Cache block storing some key and value, along with the time it was last used.
+    pub struct NamespacedBlock<K, V> {
+        pub key: K,
+        pub value: V,
+        pub stamp: u64,
+    }
+
+    /// Share of the cache capacity, in entries, set aside for a namespace. Entries of
+    /// a namespace holding no more than `min` entries are only evicted to make room
+    /// in the same namespace. A namespace never holds more than `max` entries.
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub struct NamespaceQuota {
+        pub min: usize,
+        pub max: Option<usize>,
+    }
+
+    /// Statistics for the entries of a namespace.
+    #[derive(Debug, Clone, Copy, PartialEq, Default)]
+    pub struct NamespaceStats {
+        pub hits: u64,
+        pub misses: u64,
+        pub insertions: u64,
+        pub evictions: u64,
+    }
+
+    struct Namespace<K, V> {
+        blocks: LinkedList<NamespacedBlock<K, V>>,
+        block_refs: HashMap<K, Link>,
+        quota: NamespaceQuota,
+        stats: NamespaceStats,
+    }
+
+    impl<K, V> Namespace<K, V>
+    where
+        K: Eq + Hash + Copy,
+    {
+        fn new(quota: NamespaceQuota) -> Self {
+            Namespace {
+                blocks: LinkedList::new(),
+                block_refs: HashMap::new(),
+                quota,
+                stats: NamespaceStats::default(),
+            }
+        }
+
+        /// Returns the number of entries this namespace holds beyond its guaranteed share.
+        fn excess(&self) -> usize {
+            self.blocks.len().saturating_sub(self.quota.min)
+        }
+
+        fn oldest_stamp(&self) -> Option<u64> {
+            self.blocks.peek_front().ok().map(|block| block.stamp)
+        }
+
+        fn evict(&mut self) -> Result<(), CacheError> {
+            let block = self.blocks.pop_front().map_err(CacheError::CacheBroken)?;
+            self.block_refs.remove(&block.key);
+            self.stats.evictions += 1;
+            Ok(())
+        }
+    }
+
+    /// A Least-Recently-Used Cache partitioned into namespaces with quotas.
+    pub struct NamespacedCache<N, K, V>
+    where
+        N: Eq + Hash,
+        K: Eq + Hash,
+    {
+        namespaces: HashMap<N, Namespace<K, V>>,
+        capacity: usize,
+        len: usize,
+        tick: u64,
+    }
+
+    impl<N, K, V> NamespacedCache<N, K, V>
+    where
+        N: Eq + Hash + Copy,
+        K: Eq + Hash + Copy,
+    {
+        /// Creates a NamespacedCache with the given capacity, shared by all namespaces. A
+        /// zero capacity NamespacedCache is unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            NamespacedCache {
+                namespaces: HashMap::new(),
+                capacity,
+                len: 0,
+                tick: 0,
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.len
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.len == 0
+        }
+
+        pub fn capacity(&self) -> usize {
+            self.capacity
+        }
+
+        /// Returns the number of entries in the given namespace.
+        pub fn namespace_len(&self, namespace: &N) -> usize {
+            self.namespaces
+                .get(namespace)
+                .map_or(0, |namespace| namespace.blocks.len())
+        }
+
+        /// Returns the quota of the given namespace, if it has been used.
+        pub fn quota(&self, namespace: &N) -> Option<NamespaceQuota> {
+            self.namespaces
+                .get(namespace)
+                .map(|namespace| namespace.quota)
+        }
+
+        /// Returns the statistics of the given namespace, if it has been used.
+        pub fn stats(&self, namespace: &N) -> Option<NamespaceStats> {
+            self.namespaces
+                .get(namespace)
+                .map(|namespace| namespace.stats)
+        }
+
+        /// Sets the quota of the given namespace. Namespaces used without setting a quota
+        /// have neither a guaranteed nor a maximum share. If the guaranteed shares of all
+        /// namespaces add up to more than the capacity of this cache, or the minimum share
+        /// exceeds the maximum one, we return a "quota-exceeded" error. Entries beyond a
+        /// lowered maximum share are evicted.
+        pub fn set_quota(
+            &mut self,
+            namespace: N,
+            quota: NamespaceQuota,
+        ) -> Result<(), NamespaceError> {
+            if quota.max.is_some_and(|max| max < quota.min) {
+                return Err(NamespaceError::QuotaExceeded);
+            }
+
+            let reserved: usize = self
+                .namespaces
+                .iter()
+                .filter(|(name, _)| **name != namespace)
+                .map(|(_, namespace)| namespace.quota.min)
+                .sum();
+            if reserved + quota.min > self.capacity {
+                return Err(NamespaceError::QuotaExceeded);
+            }
+
+            let namespace = self
+                .namespaces
+                .entry(namespace)
+                .or_insert_with(|| Namespace::new(quota));
+            namespace.quota = quota;
+
+            while namespace.blocks.len() > quota.max.unwrap_or(usize::MAX) {
+                namespace.evict()?;
+                self.len -= 1;
+            }
+
+            Ok(())
+        }
+
+        /// Returns a reference to the value associated with the given key in the given
+        /// namespace. If the key is not present in the namespace, we return a "cache-miss"
+        /// error. Querying a namespace never used before does not create it.
+        pub fn query(&mut self, namespace: N, key: &K) -> Result<&V, CacheError> {
+            let namespace = self
+                .namespaces
+                .get_mut(&namespace)
+                .ok_or(CacheError::CacheMiss)?;
+
+            let Some(link) = namespace.block_refs.get(key).copied() else {
+                namespace.stats.misses += 1;
+                return Err(CacheError::CacheMiss);
+            };
+
+            namespace.stats.hits += 1;
+            namespace
+                .blocks
+                .reposition_to_tail(&link)
+                .map_err(CacheError::CacheBroken)?;
+
+            self.tick += 1;
+            let block = namespace
+                .blocks
+                .get_mut_value(&link)
+                .map_err(CacheError::CacheBroken)?;
+            block.stamp = self.tick;
+            Ok(&block.value)
+        }
+
+        /// Removes the associated key value pair for the given key from the given
+        /// namespace. If no entry is found, we return a "cache-miss" error.
+        pub fn remove(&mut self, namespace: &N, key: &K) -> Result<V, CacheError> {
+            let namespace = self
+                .namespaces
+                .get_mut(namespace)
+                .ok_or(CacheError::CacheMiss)?;
+            let link = namespace
+                .block_refs
+                .remove(key)
+                .ok_or(CacheError::CacheMiss)?;
+            let block = namespace
+                .blocks
+                .remove(&link)
+                .map_err(CacheError::CacheBroken)?;
+
+            self.len -= 1;
+            Ok(block.value)
+        }
+
+        /// Evicts an entry to make room for a new one in the given namespace. A namespace
+        /// at its maximum share evicts its own least recently used entry. Otherwise, if this
+        /// cache is full, the namespace furthest over its guaranteed share gives up its
+        /// least recently used entry, with ties going to the namespace holding the oldest
+        /// entry.
+        fn make_room(&mut self, name: N) -> Result<(), NamespaceError> {
+            let capacity = self.capacity;
+            let (len, quota) = self
+                .namespaces
+                .get(&name)
+                .map_or((0, NamespaceQuota::default()), |namespace| {
+                    (namespace.blocks.len(), namespace.quota)
+                });
+
+            let max = quota.max.unwrap_or(capacity).min(capacity);
+            if len >= max {
+                let namespace = match self.namespaces.get_mut(&name) {
+                    Some(namespace) if !namespace.blocks.is_empty() => namespace,
+                    _ => return Err(NamespaceError::QuotaExceeded),
+                };
+
+                namespace.evict()?;
+                self.len -= 1;
+                return Ok(());
+            }
+
+            if self.len < capacity {
+                return Ok(());
+            }
+
+            let victim = self
+                .namespaces
+                .iter()
+                .filter(|(victim, namespace)| {
+                    namespace.excess() > 0 || (**victim == name && !namespace.blocks.is_empty())
+                })
+                .max_by_key(|(_, namespace)| {
+                    (namespace.excess(), Reverse(namespace.oldest_stamp()))
+                })
+                .map(|(victim, _)| *victim)
+                .ok_or(NamespaceError::QuotaExceeded)?;
+
+            if let Some(namespace) = self.namespaces.get_mut(&victim) {
+                namespace.evict()?;
+                self.len -= 1;
+            }
+
+            Ok(())
+        }
+
+        /// Inserts a new key value pair into the given namespace. If the namespace is at
+        /// its maximum share, or this cache is full, an entry is evicted as described for
+        /// this cache. If no entry can be evicted without breaking the quotas, we return a
+        /// "quota-exceeded" error, and a namespace never used before is not created.
+        pub fn insert(&mut self, namespace: N, key: K, value: V) -> Result<(), NamespaceError> {
+            if self.capacity == 0 {
+                return Err(CacheError::CacheBroken(ListError::ListEmpty).into());
+            }
+
+            self.tick += 1;
+            let stamp = self.tick;
+
+            if let Some(entry) = self.namespaces.get_mut(&namespace) {
+                let Some(link) = entry.block_refs.get(&key).copied() else {
+                    return self.insert_new(namespace, key, value, stamp);
+                };
+
+                entry
+                    .blocks
+                    .reposition_to_tail(&link)
+                    .map_err(CacheError::CacheBroken)?;
+                let block = entry
+                    .blocks
+                    .get_mut_value(&link)
+                    .map_err(CacheError::CacheBroken)?;
+                block.value = value;
+                block.stamp = stamp;
+                return Ok(());
+            }
+
+            self.insert_new(namespace, key, value, stamp)
+        }
+
+        /// Inserts an entry for a key not present in the given namespace, creating the
+        /// namespace once room has been made.
+        fn insert_new(
+            &mut self,
+            namespace: N,
+            key: K,
+            value: V,
+            stamp: u64,
+        ) -> Result<(), NamespaceError> {
+            self.make_room(namespace)?;
+
+            let entry = self
+                .namespaces
+                .entry(namespace)
+                .or_insert_with(|| Namespace::new(NamespaceQuota::default()));
+            if entry.blocks.is_full() {
+                entry.blocks.reserve(entry.blocks.capacity().max(1));
+            }
+
+            let link = entry
+                .blocks
+                .push_back(NamespacedBlock { key, value, stamp })
+                .map_err(CacheError::CacheBroken)?;
+            entry.block_refs.insert(key, link);
+            entry.stats.insertions += 1;
+
+            self.len += 1;
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn namespaced_cache_consistency() {
+            let mut namespaced_cache = NamespacedCache::<u8, i32, i32>::with_capacity(0);
+            assert_eq!(
+                namespaced_cache.insert(0, 0, 0),
+                Err(NamespaceError::Cache(CacheError::CacheBroken(
+                    ListError::ListEmpty
+                )))
+            );
+            assert_eq!(namespaced_cache.stats(&0), None);
+
+            let mut namespaced_cache = NamespacedCache::<u8, i32, i32>::with_capacity(6);
+            let quota = NamespaceQuota {
+                min: 3,
+                max: Some(2),
+            };
+            assert_eq!(
+                namespaced_cache.set_quota(0, quota),
+                Err(NamespaceError::QuotaExceeded)
+            );
+
+            let quota = NamespaceQuota { min: 1, max: None };
+            assert!(namespaced_cache.set_quota(0, quota).is_ok());
+            let quota = NamespaceQuota {
+                min: 0,
+                max: Some(3),
+            };
+            assert!(namespaced_cache.set_quota(1, quota).is_ok());
+
+            // namespace 1 is capped at its maximum share
+            for ele in 0..5 {
+                assert!(namespaced_cache.insert(1, ele, ele).is_ok());
+            }
+            assert_eq!(namespaced_cache.namespace_len(&1), 3);
+            assert_eq!(namespaced_cache.query(1, &1), Err(CacheError::CacheMiss));
+            assert_eq!(namespaced_cache.query(1, &2), Ok(&2));
+
+            for ele in 0..3 {
+                assert!(namespaced_cache.insert(2, ele, ele).is_ok());
+            }
+            assert_eq!(namespaced_cache.len(), 6);
+
+            // both 1 and 2 are 3 over their share; 3 in namespace 1 is the oldest entry
+            assert!(namespaced_cache.insert(0, 0, 0).is_ok());
+            assert_eq!(namespaced_cache.query(1, &3), Err(CacheError::CacheMiss));
+            assert!(namespaced_cache.insert(0, 1, 1).is_ok());
+            assert_eq!(namespaced_cache.query(2, &0), Err(CacheError::CacheMiss));
+            assert_eq!(namespaced_cache.len(), 6);
+
+            let stats = namespaced_cache.stats(&1).unwrap();
+            assert_eq!(
+                stats,
+                NamespaceStats {
+                    hits: 1,
+                    misses: 2,
+                    insertions: 5,
+                    evictions: 3,
+                }
+            );
+            assert_eq!(namespaced_cache.stats(&3), None);
+
+            // lookups of unknown namespaces do not create them
+            assert_eq!(namespaced_cache.query(3, &0), Err(CacheError::CacheMiss));
+            assert_eq!(namespaced_cache.remove(&3, &0), Err(CacheError::CacheMiss));
+            assert_eq!(namespaced_cache.stats(&3), None);
+
+            // lowering a maximum share evicts down to it
+            let quota = NamespaceQuota {
+                min: 0,
+                max: Some(1),
+            };
+            assert!(namespaced_cache.set_quota(2, quota).is_ok());
+            assert_eq!(namespaced_cache.namespace_len(&2), 1);
+            assert_eq!(namespaced_cache.query(2, &2), Ok(&2));
+            assert_eq!(namespaced_cache.len(), 5);
+
+            assert_eq!(namespaced_cache.remove(&0, &1), Ok(1));
+            assert_eq!(namespaced_cache.remove(&0, &1), Err(CacheError::CacheMiss));
+            assert_eq!(namespaced_cache.remove(&3, &1), Err(CacheError::CacheMiss));
+            assert_eq!(namespaced_cache.len(), 4);
+
+            // every namespace holding no more than its guaranteed share
+            let mut namespaced_cache = NamespacedCache::<u8, i32, i32>::with_capacity(2);
+            let quota = NamespaceQuota { min: 2, max: None };
+            assert!(namespaced_cache.set_quota(0, quota).is_ok());
+            assert!(namespaced_cache.insert(0, 0, 0).is_ok());
+            assert!(namespaced_cache.insert(0, 1, 1).is_ok());
+            assert_eq!(
+                namespaced_cache.insert(1, 0, 0),
+                Err(NamespaceError::QuotaExceeded)
+            );
+            assert_eq!(namespaced_cache.stats(&1), None);
+            assert!(namespaced_cache.insert(0, 2, 2).is_ok());
+            assert_eq!(namespaced_cache.query(0, &0), Err(CacheError::CacheMiss));
+        }
+
+        #[test]
+        fn namespaced_cache_zero_capacity() {
+            let mut namespaced_cache = NamespacedCache::<u8, i32, i32>::with_capacity(0);
+            assert_eq!(namespaced_cache.query(0, &0), Err(CacheError::CacheMiss));
+            assert_eq!(namespaced_cache.remove(&0, &0), Err(CacheError::CacheMiss));
+
+            let quota = NamespaceQuota { min: 1, max: None };
+            assert_eq!(
+                namespaced_cache.set_quota(0, quota),
+                Err(NamespaceError::QuotaExceeded)
+            );
+            assert!(namespaced_cache
+                .set_quota(0, NamespaceQuota::default())
+                .is_ok());
+            assert_eq!(
+                namespaced_cache.insert(0, 0, 0),
+                Err(NamespaceError::Cache(CacheError::CacheBroken(
+                    ListError::ListEmpty
+                )))
+            );
+            assert!(namespaced_cache.is_empty());
+
+            // a namespace with a zero maximum share takes no entries
+            let mut namespaced_cache = NamespacedCache::<u8, i32, i32>::with_capacity(2);
+            let quota = NamespaceQuota {
+                min: 0,
+                max: Some(0),
+            };
+            assert!(namespaced_cache.set_quota(0, quota).is_ok());
+            assert_eq!(
+                namespaced_cache.insert(0, 0, 0),
+                Err(NamespaceError::QuotaExceeded)
+            );
+            assert!(namespaced_cache.insert(1, 0, 0).is_ok());
+            assert_eq!(namespaced_cache.namespace_len(&0), 0);
+            assert_eq!(namespaced_cache.len(), 1);
+        }
+
+        #[test]
+        fn namespaced_cache_repeated_keys() {
+            let mut namespaced_cache = NamespacedCache::<u8, i32, i32>::with_capacity(2);
+            for ele in 0..3 {
+                assert!(namespaced_cache.insert(0, 0, ele).is_ok());
+            }
+            assert_eq!(namespaced_cache.len(), 1);
+            assert_eq!(namespaced_cache.query(0, &0), Ok(&2));
+            assert_eq!(namespaced_cache.stats(&0).unwrap().insertions, 1);
+
+            // the same key in another namespace is a separate entry
+            assert!(namespaced_cache.insert(1, 0, -1).is_ok());
+            assert_eq!(namespaced_cache.query(0, &0), Ok(&2));
+            assert_eq!(namespaced_cache.query(1, &0), Ok(&-1));
+            assert_eq!(namespaced_cache.len(), 2);
+
+            // a repeated insert into a full cache evicts nothing, and marks the entry used
+            assert!(namespaced_cache.insert(1, 0, -2).is_ok());
+            assert_eq!(namespaced_cache.len(), 2);
+            assert!(namespaced_cache.insert(2, 0, 0).is_ok());
+            assert_eq!(namespaced_cache.query(0, &0), Err(CacheError::CacheMiss));
+            assert_eq!(namespaced_cache.query(1, &0), Ok(&-2));
+
+            // nor does it at the maximum share of its namespace
+            let quota = NamespaceQuota {
+                min: 0,
+                max: Some(1),
+            };
+            assert!(namespaced_cache.set_quota(1, quota).is_ok());
+            assert!(namespaced_cache.insert(1, 0, -3).is_ok());
+            assert_eq!(namespaced_cache.query(1, &0), Ok(&-3));
+            assert_eq!(namespaced_cache.stats(&1).unwrap().evictions, 0);
+        }
+    }
+}
+
+pub mod budget {
+    //! Module providing a coordinator enforcing a global entry and weight budget over many
+    //! caches, each keeping its own key and value types and its own API. Caches are shared
+    //! with the coordinator through `Rc<RefCell<_>>` handles. When the budget is exceeded,
+    //! the cache holding the globally least recently used entry is asked to evict it.
+    //!
+    //! The budget is not enforced on insert: caches only share a clock with the
+    //! coordinator, and may exceed the budget until the caller calls
+    //! [`BudgetCoordinator::enforce`], typically after every insert into any of them.
+    //! Entries weigh one each, unless a weigher is set on the cache, such as with
+    //! [`LRUCache::set_weigher`](crate::lrucache::LRUCache::set_weigher).
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::budget::BudgetCoordinator;
+    //! use generational_lru::lrucache::{CacheError, LRUCache};
+    //! use std::{cell::RefCell, rc::Rc};
+    //!
+    //! let mut coordinator = BudgetCoordinator::with_entry_budget(3);
+    //! let users = Rc::new(RefCell::new(LRUCache::<u32, String>::with_capacity(10)));
+    //! let sessions = Rc::new(RefCell::new(LRUCache::<u64, u64>::with_capacity(10)));
+    //! assert!(coordinator.register(&users).is_ok());
+    //! assert!(coordinator.register(&sessions).is_ok());
+    //!
+    //! assert!(users.borrow_mut().insert(1, "alice".to_string()).is_ok());
+    //! assert!(sessions.borrow_mut().insert(7, 1).is_ok());
+    //! assert!(users.borrow_mut().insert(2, "bob".to_string()).is_ok());
+    //! assert!(users.borrow_mut().query(&1).is_ok());
+    //!
+    //! // the session is the globally least recently used entry
+    //! assert!(users.borrow_mut().insert(3, "carol".to_string()).is_ok());
+    //! assert_eq!(coordinator.enforce(), Ok(1));
+    //! assert_eq!(sessions.borrow_mut().query(&7), Err(CacheError::CacheMiss));
+    //! assert_eq!(coordinator.usage(), Ok((3, 3)));
+    //! ```
+
+    use crate::lrucache::CacheError;
+    use std::{
+        cell::RefCell,
+        fmt::Display,
+        rc::{Rc, Weak},
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum BudgetError {
+        Cache(CacheError),
+        CacheBusy,
+        NothingEvictable,
+    }
+
+    impl Display for BudgetError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match &self {
+                BudgetError::Cache(cache_error) => cache_error.fmt(f),
+                BudgetError::CacheBusy => write!(f, "A registered cache is borrowed."),
+                BudgetError::NothingEvictable => {
+                    write!(f, "No registered cache has an entry to evict.")
+                }
+            }
+        }
+    }
+
+    impl From<CacheError> for BudgetError {
+        fn from(cache_error: CacheError) -> Self {
+            BudgetError::Cache(cache_error)
+        }
+    }
+
+    /// Clock shared by the caches registered with one coordinator, stamping entries as
+    /// they are used so that recency compares across caches.
+    #[derive(Debug, Clone, Default)]
+    pub struct BudgetClock(Arc<AtomicU64>);
+
+    impl BudgetClock {
+        /// Advances this clock, returning the new time.
+        pub fn tick(&self) -> u64 {
+            self.0.fetch_add(1, Ordering::Relaxed) + 1
+        }
+    }
+
+    /// A cache whose entries count against the budget of a BudgetCoordinator.
+    pub trait BudgetMember {
+        /// Sets the clock to stamp entries with whenever they are used.
+        fn set_clock(&mut self, clock: BudgetClock);
+
+        /// Returns the stamp of the entry `evict_oldest` would evict, if any.
+        fn oldest_stamp(&mut self) -> Option<u64>;
+
+        /// Evicts the least recently used entry, returning its weight.
+        fn evict_oldest(&mut self) -> Result<usize, CacheError>;
+
+        /// Returns the number of entries in this cache.
+        fn entries(&self) -> usize;
+
+        /// Returns the total weight of the entries in this cache.
+        fn weight(&self) -> usize;
+    }
+
+    /// Coordinator tracking a number of caches, and evicting the globally least recently
+    /// used entry among them while their total number of entries or total weight exceeds
+    /// its budget. Caches are only held weakly, and forgotten once dropped. The budget is
+    /// only enforced when `enforce` is called.
+    pub struct BudgetCoordinator {
+        members: Vec<Weak<RefCell<dyn BudgetMember>>>,
+        clock: BudgetClock,
+
+        max_entries: usize,
+        max_weight: usize,
+    }
+
+    impl BudgetCoordinator {
+        /// Creates a BudgetCoordinator allowing at most the given number of entries, of at
+        /// most the given total weight, across all caches. A zero budget keeps every
+        /// registered cache empty.
+        pub fn with_budgets(max_entries: usize, max_weight: usize) -> Self {
+            BudgetCoordinator {
+                members: Vec::new(),
+                clock: BudgetClock::default(),
+                max_entries,
+                max_weight,
+            }
+        }
+
+        /// Creates a BudgetCoordinator allowing at most the given number of entries.
+        pub fn with_entry_budget(max_entries: usize) -> Self {
+            Self::with_budgets(max_entries, usize::MAX)
+        }
+
+        /// Creates a BudgetCoordinator allowing entries of at most the given total weight.
+        pub fn with_weight_budget(max_weight: usize) -> Self {
+            Self::with_budgets(usize::MAX, max_weight)
+        }
+
+        /// Registers the given cache with this coordinator, handing it the shared clock.
+        /// Registering a cache again has no effect. If the cache is borrowed at the time,
+        /// we return a "cache-busy" error.
+        pub fn register<C>(&mut self, cache: &Rc<RefCell<C>>) -> Result<(), BudgetError>
+        where
+            C: BudgetMember + 'static,
+        {
+            let address = Rc::as_ptr(cache) as *const ();
+            if self
+                .members
+                .iter()
+                .any(|member| member.as_ptr() as *const () == address)
+            {
+                return Ok(());
+            }
+
+            cache
+                .try_borrow_mut()
+                .map_err(|_| BudgetError::CacheBusy)?
+                .set_clock(self.clock.clone());
+
+            let member: Rc<RefCell<dyn BudgetMember>> = cache.clone();
+            self.members.push(Rc::downgrade(&member));
+            Ok(())
+        }
+
+        /// Returns the registered caches still alive, forgetting the dropped ones.
+        fn live_members(&mut self) -> Vec<Rc<RefCell<dyn BudgetMember>>> {
+            self.members.retain(|member| member.strong_count() > 0);
+            self.members.iter().filter_map(Weak::upgrade).collect()
+        }
+
+        /// Returns the number of entries and their total weight across all registered
+        /// caches. If a cache is borrowed at the time, we return a "cache-busy" error.
+        pub fn usage(&mut self) -> Result<(usize, usize), BudgetError> {
+            let mut usage = (0, 0);
+            for member in self.live_members() {
+                let member = member.try_borrow().map_err(|_| BudgetError::CacheBusy)?;
+                usage.0 += member.entries();
+                usage.1 += member.weight();
+            }
+
+            Ok(usage)
+        }
+
+        /// Evicts the globally least recently used entries until the registered caches are
+        /// within budget, returning the number of entries evicted. Meant to be called after
+        /// inserting into any of them. If a cache is borrowed at the time, we return a
+        /// "cache-busy" error. If the budget is exceeded but no cache has an entry to evict,
+        /// we return a "nothing-evictable" error.
+        pub fn enforce(&mut self) -> Result<usize, BudgetError> {
+            let (mut entries, mut weight) = self.usage()?;
+            let members = self.live_members();
+
+            // only the cache evicted from has its oldest stamp looked up again
+            let oldest_stamp = |member: &Rc<RefCell<dyn BudgetMember>>| {
+                member
+                    .try_borrow_mut()
+                    .map(|mut member| member.oldest_stamp())
+                    .map_err(|_| BudgetError::CacheBusy)
+            };
+            let mut stamps = members
+                .iter()
+                .map(oldest_stamp)
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut evicted = 0;
+            while entries > self.max_entries || weight > self.max_weight {
+                let (index, _) = stamps
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, stamp)| stamp.map(|stamp| (index, stamp)))
+                    .min_by_key(|&(_, stamp)| stamp)
+                    .ok_or(BudgetError::NothingEvictable)?;
+
+                let victim = &members[index];
+                weight -= victim
+                    .try_borrow_mut()
+                    .map_err(|_| BudgetError::CacheBusy)?
+                    .evict_oldest()?;
+                stamps[index] = oldest_stamp(victim)?;
+                entries -= 1;
+                evicted += 1;
+            }
+
+            Ok(evicted)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::lrucache::{EvictionPolicy, LRUCache};
+
+        #[test]
+        fn budget_coordinator_consistency() {
+            let mut coordinator = BudgetCoordinator::with_entry_budget(3);
+            assert_eq!(coordinator.usage(), Ok((0, 0)));
+            let numbers = Rc::new(RefCell::new(LRUCache::<i32, i32>::with_capacity(4)));
+            let names = Rc::new(RefCell::new(LRUCache::<u8, &str>::with_capacity(4)));
+            assert!(coordinator.register(&numbers).is_ok());
+            assert!(coordinator.register(&names).is_ok());
+
+            assert!(numbers.borrow_mut().insert(0, 0).is_ok());
+            assert!(names.borrow_mut().insert(0, "zero").is_ok());
+            assert!(numbers.borrow_mut().insert(1, 1).is_ok());
+            assert_eq!(numbers.borrow_mut().query(&0), Ok(&0));
+            assert_eq!(coordinator.enforce(), Ok(0));
+
+            // the name is used least recently, although numbers hold more entries
+            assert!(numbers.borrow_mut().insert(2, 2).is_ok());
+            assert_eq!(coordinator.enforce(), Ok(1));
+            assert_eq!(names.borrow_mut().query(&0), Err(CacheError::CacheMiss));
+            assert!(names.borrow_mut().insert(1, "one").is_ok());
+            assert_eq!(coordinator.enforce(), Ok(1));
+            assert_eq!(numbers.borrow_mut().query(&1), Err(CacheError::CacheMiss));
+            assert_eq!(coordinator.usage(), Ok((3, 3)));
+
+            // pinned entries are passed over, and a borrowed cache is never touched
+            assert!(numbers.borrow_mut().pin(&0).is_ok());
+            assert!(numbers.borrow_mut().insert(3, 3).is_ok());
+            let borrowed = names.borrow_mut();
+            assert_eq!(coordinator.enforce(), Err(BudgetError::CacheBusy));
+            drop(borrowed);
+            assert_eq!(coordinator.enforce(), Ok(1));
+            assert_eq!(numbers.borrow_mut().query(&2), Err(CacheError::CacheMiss));
+            assert_eq!(numbers.borrow_mut().query(&0), Ok(&0));
+
+            // dropped caches are forgotten
+            drop(names);
+            assert_eq!(coordinator.usage(), Ok((2, 2)));
+
+            // a zero weight budget evicts everything but pinned entries
+            let mut coordinator = BudgetCoordinator::with_budgets(4, 0);
+            assert!(coordinator.register(&numbers).is_ok());
+            assert!(numbers.borrow_mut().pin(&3).is_ok());
+            assert_eq!(coordinator.enforce(), Err(BudgetError::NothingEvictable));
+            assert!(numbers.borrow_mut().unpin(&0).is_ok());
+            assert!(numbers.borrow_mut().unpin(&3).is_ok());
+            assert_eq!(coordinator.enforce(), Ok(2));
+            assert_eq!(coordinator.usage(), Ok((0, 0)));
+        }
+
+        #[test]
+        fn budget_coordinator_weights() {
+            let mut coordinator = BudgetCoordinator::with_weight_budget(10);
+            let blobs = Rc::new(RefCell::new(LRUCache::<u8, Vec<u8>>::with_capacity(4)));
+            blobs.borrow_mut().set_weigher(|_, blob| blob.len());
+            assert!(coordinator.register(&blobs).is_ok());
+
+            assert!(blobs.borrow_mut().insert(0, vec![0; 4]).is_ok());
+            assert!(blobs.borrow_mut().insert(1, vec![1; 4]).is_ok());
+            assert_eq!(coordinator.enforce(), Ok(0));
+            assert_eq!(coordinator.usage(), Ok((2, 8)));
+
+            // a single heavy entry pushes out both light ones
+            assert!(blobs.borrow_mut().insert(2, vec![2; 9]).is_ok());
+            assert_eq!(coordinator.enforce(), Ok(2));
+            assert_eq!(coordinator.usage(), Ok((1, 9)));
+
+            // an entry heavier than the whole budget is evicted too
+            assert!(blobs.borrow_mut().insert(3, vec![3; 11]).is_ok());
+            assert_eq!(coordinator.enforce(), Ok(2));
+            assert_eq!(coordinator.usage(), Ok((0, 0)));
+        }
+
+        #[test]
+        fn budget_coordinator_eviction_order() {
+            // entries in the victim buffer count, and are evicted first
+            let mut coordinator = BudgetCoordinator::with_entry_budget(3);
+            let numbers = Rc::new(RefCell::new(LRUCache::<i32, i32>::with_capacity(2)));
+            numbers.borrow_mut().set_victim_capacity(2);
+            assert!(coordinator.register(&numbers).is_ok());
+            for ele in 0..3 {
+                assert!(numbers.borrow_mut().insert(ele, ele).is_ok());
+            }
+            assert_eq!(coordinator.usage(), Ok((3, 3)));
+            assert!(numbers.borrow_mut().insert(3, 3).is_ok());
+            assert_eq!(coordinator.enforce(), Ok(1));
+            assert!(!numbers.borrow().contains(&0));
+            assert!(numbers.borrow().contains(&1));
+
+            // an MRU cache gives up its most recently used entry
+            let mut coordinator = BudgetCoordinator::with_entry_budget(2);
+            let numbers = Rc::new(RefCell::new(
+                LRUCache::<i32, i32>::with_capacity_and_policy(4, EvictionPolicy::MostRecentlyUsed),
+            ));
+            assert!(coordinator.register(&numbers).is_ok());
+            for ele in 0..3 {
+                assert!(numbers.borrow_mut().insert(ele, ele).is_ok());
+            }
+            assert_eq!(coordinator.enforce(), Ok(1));
+            assert!(numbers.borrow().peek_lru().is_some());
+            assert!(!numbers.borrow().contains(&2));
+
+            // pinned entries are skipped until unpinned
+            let mut coordinator = BudgetCoordinator::with_entry_budget(2);
+            let numbers = Rc::new(RefCell::new(LRUCache::<i32, i32>::with_capacity(8)));
+            assert!(coordinator.register(&numbers).is_ok());
+            for ele in 0..4 {
+                assert!(numbers.borrow_mut().insert(ele, ele).is_ok());
+            }
+            assert!(numbers.borrow_mut().pin(&0).is_ok());
+            assert!(numbers.borrow_mut().pin(&1).is_ok());
+            assert_eq!(coordinator.enforce(), Ok(2));
+            assert!(numbers.borrow().contains(&0) && numbers.borrow().contains(&1));
+
+            assert!(numbers.borrow_mut().unpin(&0).is_ok());
+            assert!(numbers.borrow_mut().insert(4, 4).is_ok());
+            assert_eq!(coordinator.enforce(), Ok(1));
+            assert!(!numbers.borrow().contains(&0));
+            assert!(numbers.borrow().contains(&1) && numbers.borrow().contains(&4));
+        }
+
+        #[test]
+        fn budget_coordinator_zero_budget() {
+            let mut coordinator = BudgetCoordinator::with_entry_budget(0);
+            assert_eq!(coordinator.enforce(), Ok(0));
+
+            let numbers = Rc::new(RefCell::new(LRUCache::<i32, i32>::with_capacity(4)));
+            let borrowed = numbers.borrow();
+            assert_eq!(coordinator.register(&numbers), Err(BudgetError::CacheBusy));
+            drop(borrowed);
+            assert!(coordinator.register(&numbers).is_ok());
+            assert_eq!(coordinator.enforce(), Ok(0));
+
+            for ele in 0..3 {
+                assert!(numbers.borrow_mut().insert(ele, ele).is_ok());
+            }
+            assert_eq!(coordinator.enforce(), Ok(3));
+            assert!(numbers.borrow().is_empty());
+
+            // a zero capacity cache holds nothing to evict
+            let empty = Rc::new(RefCell::new(LRUCache::<i32, i32>::with_capacity(0)));
+            assert!(coordinator.register(&empty).is_ok());
+            assert!(empty.borrow_mut().insert(0, 0).is_err());
+            assert_eq!(coordinator.enforce(), Ok(0));
+            assert_eq!(coordinator.usage(), Ok((0, 0)));
+        }
+
+        #[test]
+        fn budget_coordinator_repeated_keys() {
+            let mut coordinator = BudgetCoordinator::with_entry_budget(2);
+            let numbers = Rc::new(RefCell::new(LRUCache::<i32, i32>::with_capacity(4)));
+            let names = Rc::new(RefCell::new(LRUCache::<u8, &str>::with_capacity(4)));
+            assert!(coordinator.register(&numbers).is_ok());
+            assert!(coordinator.register(&names).is_ok());
+
+            // registering again does not count the entries twice
+            assert!(coordinator.register(&numbers).is_ok());
+            assert_eq!(coordinator.members.len(), 2);
+
+            for ele in 0..3 {
+                assert!(numbers.borrow_mut().insert(0, ele).is_ok());
+            }
+            assert_eq!(coordinator.usage(), Ok((1, 1)));
+
+            // a repeated insert counts as a use of the entry
+            assert!(names.borrow_mut().insert(0, "zero").is_ok());
+            assert!(numbers.borrow_mut().insert(0, 3).is_ok());
+            assert!(names.borrow_mut().insert(1, "one").is_ok());
+            assert_eq!(coordinator.enforce(), Ok(1));
+            assert_eq!(names.borrow_mut().query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(numbers.borrow_mut().query(&0), Ok(&3));
+        }
+    }
+}
+
+pub mod tagged {
+    //! Module providing a Least-Recently-Used Cache whose entries carry tags, so that all
+    //! entries sharing a tag can be invalidated at once through a secondary index.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::tagged::TaggedCache;
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! let mut tagged_cache = TaggedCache::<u32, &str, &str>::with_capacity(10);
+    //!
+    //! assert!(tagged_cache.insert(1, "order 1", ["user:42", "table:orders"]).is_ok());
+    //! assert!(tagged_cache.insert(2, "order 2", ["user:7", "table:orders"]).is_ok());
+    //! assert!(tagged_cache.insert(3, "profile", ["user:42"]).is_ok());
+    //!
+    //! let mut removed = tagged_cache.invalidate_tag(&"user:42").unwrap();
+    //! removed.sort();
+    //! assert_eq!(removed, [(1, "order 1"), (3, "profile")]);
+    //!
+    //! assert_eq!(tagged_cache.query(&1), Err(CacheError::CacheMiss));
+    //! assert_eq!(tagged_cache.query(&2), Ok(&"order 2"));
+    //! assert_eq!(tagged_cache.tag_len(&"table:orders"), 1);
+    //! ```
+
+    use crate::lrucache::{CacheError, LRUCache};
+    use std::{
+        collections::{HashMap, HashSet},
+        hash::Hash,
+    };
+
+    /// A Least-Recently-Used Cache with tagged entries. Entries are kept in an LRUCache
+    /// along with their tags, and indexed by tag on the side.
+    pub struct TaggedCache<K, V, T>
+    where
+        K: Eq + Hash,
+        T: Eq + Hash,
+    {
+        cache: LRUCache<K, (V, Vec<T>)>,
+        tag_refs: HashMap<T, HashSet<K>>,
+    }
+
+    impl<K, V, T> TaggedCache<K, V, T>
+    where
+        K: Eq + Hash + Copy,
+        T: Eq + Hash + Clone,
+    {
+        /// Creates a TaggedCache with the given capacity. A zero capacity TaggedCache is
+        /// unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            TaggedCache {
+                cache: LRUCache::with_capacity(capacity),
+                tag_refs: HashMap::new(),
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.cache.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.cache.is_empty()
+        }
+
+        /// Returns the number of entries carrying the given tag.
+        pub fn tag_len(&self, tag: &T) -> usize {
+            self.tag_refs.get(tag).map_or(0, HashSet::len)
+        }
+
+        /// Returns the tags of the entry for the given key. If the key is not present in
+        /// the cache, we return a "cache-miss" error.
+        pub fn tags(&self, key: &K) -> Result<&[T], CacheError> {
+            let (_, tags) = self.cache.peek(key)?;
+            Ok(tags)
+        }
+
+        fn index_tags(&mut self, key: K, tags: &[T]) {
+            for tag in tags {
+                self.tag_refs.entry(tag.clone()).or_default().insert(key);
+            }
+        }
+
+        fn unindex_tags(&mut self, key: &K, tags: &[T]) {
+            for tag in tags {
+                if let Some(keys) = self.tag_refs.get_mut(tag) {
+                    keys.remove(key);
+                    if keys.is_empty() {
+                        self.tag_refs.remove(tag);
+                    }
+                }
+            }
+        }
+
+        /// Returns a reference to the value associated with the given key. If the key is not
+        /// present in the cache, we return a "cache-miss" error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let (value, _) = self.cache.query(key)?;
+            Ok(value)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            let (value, tags) = self.cache.remove(key)?;
+            self.unindex_tags(key, &tags);
+            Ok(value)
+        }
+
+        /// Removes every entry carrying the given tag, returning the removed key value
+        /// pairs, in no particular order.
+        pub fn invalidate_tag(&mut self, tag: &T) -> Result<Vec<(K, V)>, CacheError> {
+            let keys = self.tag_refs.remove(tag).unwrap_or_default();
+
+            let mut removed = Vec::with_capacity(keys.len());
+            for key in keys {
+                let value = self.remove(&key)?;
+                removed.push((key, value));
+            }
+
+            Ok(removed)
+        }
+
+        /// Inserts a new key value pair with the given tags into this cache. The tags of an
+        /// existing entry for the key are replaced. If this cache is full, the least
+        /// recently used entry is removed, along with its tags.
+        pub fn insert<I>(&mut self, key: K, value: V, tags: I) -> Result<(), CacheError>
+        where
+            I: IntoIterator<Item = T>,
+        {
+            let tags: Vec<T> = tags.into_iter().collect();
+            let old_tags = match self.cache.peek(&key) {
+                Ok((_, old_tags)) => old_tags.clone(),
+                Err(_) => Vec::new(),
+            };
+
+            let evicted = self.cache.push(key, (value, tags.clone()))?;
+
+            self.unindex_tags(&key, &old_tags);
+            if let Some((evicted_key, (_, evicted_tags))) = evicted {
+                self.unindex_tags(&evicted_key, &evicted_tags);
+            }
+            self.index_tags(key, &tags);
+
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::list::ListError;
+
+        #[test]
+        fn tagged_cache_consistency() {
+            let mut tagged_cache = TaggedCache::<i32, i32, char>::with_capacity(3);
+            assert!(tagged_cache.insert(0, 0, ['a', 'b']).is_ok());
+            assert!(tagged_cache.insert(1, 1, ['a']).is_ok());
+            assert!(tagged_cache.insert(2, 2, []).is_ok());
+            assert_eq!(tagged_cache.tags(&0), Ok(&['a', 'b'][..]));
+
+            // evictions drop entries from the tag index
+            assert!(tagged_cache.insert(3, 3, ['b']).is_ok());
+            assert_eq!(tagged_cache.tag_len(&'a'), 1);
+            assert_eq!(tagged_cache.tag_len(&'b'), 1);
+
+            // re-tagging an entry replaces its tags
+            assert!(tagged_cache.insert(1, -1, ['b', 'c']).is_ok());
+            assert_eq!(tagged_cache.tag_len(&'a'), 0);
+            assert!(!tagged_cache.tag_refs.contains_key(&'a'));
+            assert_eq!(tagged_cache.invalidate_tag(&'a'), Ok(Vec::new()));
+
+            assert_eq!(tagged_cache.remove(&3), Ok(3));
+            assert_eq!(tagged_cache.tag_len(&'b'), 1);
+            assert_eq!(tagged_cache.invalidate_tag(&'c'), Ok(vec![(1, -1)]));
+            assert!(tagged_cache.tag_refs.is_empty());
+            assert_eq!(tagged_cache.query(&1), Err(CacheError::CacheMiss));
+            assert_eq!(tagged_cache.query(&2), Ok(&2));
+            assert_eq!(tagged_cache.len(), 1);
+            assert_eq!(tagged_cache.tags(&3), Err(CacheError::CacheMiss));
+        }
+
+        #[test]
+        fn tagged_cache_zero_capacity() {
+            let mut tagged_cache = TaggedCache::<i32, i32, char>::with_capacity(0);
+            assert_eq!(tagged_cache.query(&0), Err(CacheError::CacheMiss));
+            assert_eq!(tagged_cache.remove(&0), Err(CacheError::CacheMiss));
+            assert_eq!(tagged_cache.invalidate_tag(&'a'), Ok(Vec::new()));
+
+            // a failed insert leaves nothing in the tag index
+            assert_eq!(
+                tagged_cache.insert(0, 0, ['a']),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+            assert_eq!(tagged_cache.tag_len(&'a'), 0);
+            assert!(tagged_cache.tag_refs.is_empty());
+            assert!(tagged_cache.is_empty());
+        }
+
+        #[test]
+        fn tagged_cache_repeated_keys() {
+            let mut tagged_cache = TaggedCache::<i32, i32, char>::with_capacity(2);
+            for ele in 0..3 {
+                assert!(tagged_cache.insert(0, ele, ['a', 'a']).is_ok());
+            }
+            assert_eq!(tagged_cache.len(), 1);
+            assert_eq!(tagged_cache.tag_len(&'a'), 1);
+            assert_eq!(tagged_cache.query(&0), Ok(&2));
+
+            // a repeated insert into a full cache evicts nothing, and marks the entry used
+            assert!(tagged_cache.insert(1, 1, ['a']).is_ok());
+            assert!(tagged_cache.insert(0, 3, ['b']).is_ok());
+            assert_eq!(tagged_cache.tag_len(&'a'), 1);
+            assert!(tagged_cache.insert(2, 2, ['b']).is_ok());
+            assert_eq!(tagged_cache.query(&1), Err(CacheError::CacheMiss));
+            assert!(!tagged_cache.tag_refs.contains_key(&'a'));
+
+            // entries carrying a tag more than once are removed once
+            assert_eq!(tagged_cache.remove(&0), Ok(3));
+            assert!(tagged_cache.insert(0, 0, ['b', 'b']).is_ok());
+            let mut removed = tagged_cache.invalidate_tag(&'b').unwrap();
+            removed.sort();
+            assert_eq!(removed, [(0, 0), (2, 2)]);
+            assert!(tagged_cache.is_empty());
+            assert!(tagged_cache.tag_refs.is_empty());
+        }
+    }
+}
+
+pub mod ordered {
+    //! Module providing a Least-Recently-Used Cache which keeps its keys ordered, for
+    //! iterating over and invalidating ranges of keys, such as all paths under a prefix.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::ordered::OrderedLRUCache;
+    //! use generational_lru::lrucache::CacheError;
+    //!
+    //! let mut ordered_cache = OrderedLRUCache::<String, i32>::with_capacity(10);
+    //!
+    //! for (ele, path) in ["/tenant/a/obj/1", "/tenant/a/obj/2", "/tenant/b/obj/1"]
+    //!     .iter()
+    //!     .enumerate()
+    //! {
+    //!     assert!(ordered_cache.insert(path.to_string(), ele as i32).is_ok());
+    //! }
+    //!
+    //! let removed = ordered_cache.remove_prefix("/tenant/a/").unwrap();
+    //! assert_eq!(removed.len(), 2);
+    //! assert_eq!(
+    //!     ordered_cache.query(&"/tenant/a/obj/1".to_string()),
+    //!     Err(CacheError::CacheMiss)
+    //! );
+    //!
+    //! let keys: Vec<_> = ordered_cache.range(..).map(|(key, _)| key.as_str()).collect();
+    //! assert_eq!(keys, ["/tenant/b/obj/1"]);
+    //! ```
+
+    use crate::list::{Link, LinkedList};
+    use crate::lrucache::{Block, CacheError};
+    use std::{
+        borrow::Borrow,
+        collections::BTreeMap,
+        ops::{Bound, RangeBounds},
+    };
+
+    /// A Least-Recently-Used Cache keeping its keys ordered, implemented using a
+    /// generational arena based linked list and an ordered map. The ordered map takes the
+    /// place of the hash map of an LRUCache, so keys need not be hashable.
+    pub struct OrderedLRUCache<K, V>
+    where
+        K: Ord,
+    {
+        blocks: LinkedList<Block<K, V>>,
+        block_refs: BTreeMap<K, Link>,
+    }
+
+    impl<K, V> OrderedLRUCache<K, V>
+    where
+        K: Ord + Clone,
+    {
+        /// Creates an OrderedLRUCache with the given capacity. A zero capacity
+        /// OrderedLRUCache is unusable.
+        pub fn with_capacity(capacity: usize) -> Self {
+            OrderedLRUCache {
+                blocks: LinkedList::with_capacity(capacity),
+                block_refs: BTreeMap::new(),
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.blocks.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.blocks.is_empty()
+        }
+
+        /// Returns a reference to the value associated with the given key. If the key is not
+        /// present in the cache, we return a "cache-miss" error.
+        pub fn query(&mut self, key: &K) -> Result<&V, CacheError> {
+            let link = self.block_refs.get(key).ok_or(CacheError::CacheMiss)?;
+            self.blocks
+                .reposition_to_tail(link)
+                .map_err(CacheError::CacheBroken)?;
+            let node = self.blocks.get(link).map_err(CacheError::CacheBroken)?;
+            Ok(&node.value.value)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error.
+        pub fn remove(&mut self, key: &K) -> Result<V, CacheError> {
+            let link = self.block_refs.remove(key).ok_or(CacheError::CacheMiss)?;
+            let block = self.blocks.remove(&link).map_err(CacheError::CacheBroken)?;
+            Ok(block.value)
+        }
+
+        /// Inserts a new key value pair into this cache. If this cache is full, the least
+        /// recently used entry is removed.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), CacheError> {
+            if let Some(link) = self.block_refs.get(&key) {
                 self.blocks
                     .reposition_to_tail(link)
                     .map_err(CacheError::CacheBroken)?;
-                let block_ref = self
-                    .blocks
-                    .get_mut_value(link)
+                let block = self
+                    .blocks
+                    .get_mut_value(link)
+                    .map_err(CacheError::CacheBroken)?;
+                block.value = value;
+                return Ok(());
+            }
+
+            if self.blocks.is_full() {
+                let block = self.blocks.pop_front().map_err(CacheError::CacheBroken)?;
+                self.block_refs.remove(&block.key);
+            }
+
+            let link = self
+                .blocks
+                .push_back(Block {
+                    key: key.clone(),
+                    value,
+                })
+                .map_err(CacheError::CacheBroken)?;
+            self.block_refs.insert(key, link);
+
+            Ok(())
+        }
+
+        /// Iterates over the entries with keys in the given range, in key order, without
+        /// marking them as used.
+        pub fn range<R>(&self, range: R) -> impl Iterator<Item = (&K, &V)>
+        where
+            R: RangeBounds<K>,
+        {
+            self.block_refs
+                .range(range)
+                .filter_map(|(key, link)| Some((key, &self.blocks.get(link).ok()?.value.value)))
+        }
+
+        fn remove_keys(&mut self, keys: Vec<K>) -> Result<Vec<(K, V)>, CacheError> {
+            let mut removed = Vec::with_capacity(keys.len());
+            for key in keys {
+                let value = self.remove(&key)?;
+                removed.push((key, value));
+            }
+
+            Ok(removed)
+        }
+
+        /// Removes every entry with a key in the given range, returning the removed key
+        /// value pairs in key order.
+        pub fn remove_range<R>(&mut self, range: R) -> Result<Vec<(K, V)>, CacheError>
+        where
+            R: RangeBounds<K>,
+        {
+            let keys = self.block_refs.range(range).map(|(key, _)| key.clone());
+            self.remove_keys(keys.collect())
+        }
+
+        /// Removes every entry with a key starting with the given prefix, returning the
+        /// removed key value pairs in key order. Keys must be ordered by their bytes, as
+        /// strings and byte slices are.
+        pub fn remove_prefix<Q>(&mut self, prefix: &Q) -> Result<Vec<(K, V)>, CacheError>
+        where
+            K: Borrow<Q>,
+            Q: Ord + AsRef<[u8]> + ?Sized,
+        {
+            let keys = self
+                .block_refs
+                .range::<Q, _>((Bound::Included(prefix), Bound::Unbounded))
+                .map(|(key, _)| key)
+                .take_while(|key| {
+                    let key: &Q = (*key).borrow();
+                    key.as_ref().starts_with(prefix.as_ref())
+                })
+                .cloned();
+            self.remove_keys(keys.collect())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::list::ListError;
+
+        #[test]
+        fn ordered_lru_cache_consistency() {
+            let mut ordered_cache = OrderedLRUCache::<i32, i32>::with_capacity(5);
+            for ele in [4, 0, 3, 1, 2] {
+                assert!(ordered_cache.insert(ele, ele * 10).is_ok());
+            }
+            assert_eq!(ordered_cache.query(&4), Ok(&40));
+
+            // eviction follows recency, not key order
+            assert!(ordered_cache.insert(5, 50).is_ok());
+            assert_eq!(ordered_cache.query(&0), Err(CacheError::CacheMiss));
+            assert!(ordered_cache
+                .range(2..)
+                .eq([(&2, &20), (&3, &30), (&4, &40), (&5, &50)]));
+
+            let removed = ordered_cache.remove_range(2..=3).unwrap();
+            assert_eq!(removed, [(2, 20), (3, 30)]);
+            assert_eq!(ordered_cache.remove_range(..1), Ok(Vec::new()));
+            assert!(ordered_cache.range(..).map(|(key, _)| *key).eq([1, 4, 5]));
+            assert_eq!(ordered_cache.len(), 3);
+            assert_eq!(ordered_cache.remove(&1), Ok(10));
+
+            let mut path_cache = OrderedLRUCache::<&str, i32>::with_capacity(5);
+            for (ele, path) in ["/a", "/a/x", "/a/y", "/ab", "/b"].iter().enumerate() {
+                assert!(path_cache.insert(path, ele as i32).is_ok());
+            }
+
+            let removed = path_cache.remove_prefix("/a/").unwrap();
+            assert_eq!(removed, [("/a/x", 1), ("/a/y", 2)]);
+            let removed = path_cache.remove_prefix("/a").unwrap();
+            assert_eq!(removed, [("/a", 0), ("/ab", 3)]);
+            assert_eq!(path_cache.remove_prefix("/c"), Ok(Vec::new()));
+            assert_eq!(path_cache.query(&"/b"), Ok(&4));
+            assert_eq!(path_cache.len(), 1);
+        }
+
+        #[test]
+        fn ordered_lru_cache_unhashable_keys() {
+            #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+            struct Version(u32, u32);
+
+            let mut version_cache = OrderedLRUCache::<Version, &str>::with_capacity(3);
+            assert!(version_cache.insert(Version(1, 2), "b").is_ok());
+            assert!(version_cache.insert(Version(1, 0), "a").is_ok());
+            assert!(version_cache.insert(Version(2, 0), "c").is_ok());
+            assert!(version_cache
+                .range(Version(1, 0)..Version(2, 0))
+                .map(|(_, value)| *value)
+                .eq(["a", "b"]));
+
+            assert_eq!(version_cache.query(&Version(1, 2)), Ok(&"b"));
+            assert!(version_cache.insert(Version(3, 0), "d").is_ok());
+            assert_eq!(
+                version_cache.query(&Version(1, 0)),
+                Err(CacheError::CacheMiss)
+            );
+        }
+
+        #[test]
+        fn ordered_lru_cache_zero_capacity() {
+            let mut ordered_cache = OrderedLRUCache::<&str, i32>::with_capacity(0);
+            assert_eq!(ordered_cache.query(&"/a"), Err(CacheError::CacheMiss));
+            assert_eq!(ordered_cache.remove(&"/a"), Err(CacheError::CacheMiss));
+            assert_eq!(
+                ordered_cache.insert("/a", 0),
+                Err(CacheError::CacheBroken(ListError::ListEmpty))
+            );
+            assert_eq!(ordered_cache.range(..).count(), 0);
+            assert_eq!(ordered_cache.remove_range(..), Ok(Vec::new()));
+            assert_eq!(ordered_cache.remove_prefix("/"), Ok(Vec::new()));
+            assert!(ordered_cache.block_refs.is_empty());
+            assert!(ordered_cache.is_empty());
+        }
+
+        #[test]
+        fn ordered_lru_cache_repeated_keys() {
+            let mut ordered_cache = OrderedLRUCache::<i32, i32>::with_capacity(2);
+            for ele in 0..3 {
+                assert!(ordered_cache.insert(0, ele).is_ok());
+            }
+            assert_eq!(ordered_cache.len(), 1);
+            assert!(ordered_cache.range(..).eq([(&0, &2)]));
+
+            // a repeated insert into a full cache evicts nothing, and marks the entry used
+            assert!(ordered_cache.insert(1, 1).is_ok());
+            assert!(ordered_cache.insert(0, 3).is_ok());
+            assert_eq!(ordered_cache.len(), 2);
+            assert!(ordered_cache.insert(2, 2).is_ok());
+            assert_eq!(ordered_cache.query(&1), Err(CacheError::CacheMiss));
+            assert!(ordered_cache.range(..).eq([(&0, &3), (&2, &2)]));
+
+            // ranges removed twice come back empty the second time
+            assert_eq!(ordered_cache.remove_range(0..=0), Ok(vec![(0, 3)]));
+            assert_eq!(ordered_cache.remove_range(0..=0), Ok(Vec::new()));
+            assert_eq!(ordered_cache.len(), 1);
+            assert_eq!(ordered_cache.block_refs.len(), 1);
+        }
+    }
+}
+
+pub mod store {
+    //! Module providing caches over a backing store. Misses are loaded from the store. The
+    //! read-through, write-through cache writes to the store before the cache, while the
+    //! write-back cache keeps writes in memory until they are flushed or evicted.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::store::{CacheStore, StoreError, ThroughCache};
+    //! use generational_lru::lrucache::CacheError;
+    //! use std::collections::HashMap;
+    //!
+    //! struct MapStore(HashMap<i32, i32>);
+    //!
+    //! impl CacheStore<i32, i32> for MapStore {
+    //!     type Error = String;
+    //!
+    //!     fn load(&mut self, key: &i32) -> Result<Option<i32>, String> {
+    //!         Ok(self.0.get(key).copied())
+    //!     }
+    //!
+    //!     fn store(&mut self, key: &i32, value: &i32) -> Result<(), String> {
+    //!         if *value < 0 {
+    //!             return Err("negative values are not stored".to_string());
+    //!         }
+    //!         self.0.insert(*key, *value);
+    //!         Ok(())
+    //!     }
+    //!
+    //!     fn delete(&mut self, key: &i32) -> Result<(), String> {
+    //!         self.0.remove(key);
+    //!         Ok(())
+    //!     }
+    //! }
+    //!
+    //! let store = MapStore(HashMap::from([(0, 0)]));
+    //! let mut through_cache = ThroughCache::with_capacity(2, store);
+    //!
+    //! // read-through
+    //! assert_eq!(through_cache.get(&0), Ok(&0));
+    //! assert_eq!(through_cache.get(&1), Err(StoreError::Cache(CacheError::CacheMiss)));
+    //!
+    //! // write-through
+    //! assert!(through_cache.insert(1, 1).is_ok());
+    //! assert_eq!(through_cache.store().0.get(&1), Some(&1));
+    //! assert!(matches!(through_cache.insert(2, -2), Err(StoreError::Store(_))));
+    //! assert!(!through_cache.cache().contains(&2));
+    //!
+    //! assert_eq!(through_cache.remove(&0), Ok(Some(0)));
+    //! assert_eq!(through_cache.get(&0), Err(StoreError::Cache(CacheError::CacheMiss)));
+    //! ```
+
+    use crate::list::ListError;
+    use crate::lrucache::{CacheError, LRUCache};
+    use std::{
+        collections::HashMap,
+        fmt::Display,
+        hash::Hash,
+        sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+        thread::{self, JoinHandle},
+        time::{Duration, Instant},
+    };
+
+    /// A backing store for a cache, such as a database or a directory of files.
+    pub trait CacheStore<K, V> {
+        type Error;
+
+        /// Loads the value for the given key, if the store has one.
+        fn load(&mut self, key: &K) -> Result<Option<V>, Self::Error>;
+
+        /// Stores the given value for the given key.
+        fn store(&mut self, key: &K, value: &V) -> Result<(), Self::Error>;
+
+        /// Deletes the value for the given key. Deleting an absent key is not an error.
+        fn delete(&mut self, key: &K) -> Result<(), Self::Error>;
+    }
+
+    /// Error from a cache over a backing store, coming either from the cache or from the
+    /// store.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum StoreError<E> {
+        Cache(CacheError),
+        Store(E),
+    }
+
+    impl<E> From<CacheError> for StoreError<E> {
+        fn from(cache_error: CacheError) -> Self {
+            StoreError::Cache(cache_error)
+        }
+    }
+
+    impl<E: Display> Display for StoreError<E> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match &self {
+                StoreError::Cache(cache_error) => cache_error.fmt(f),
+                StoreError::Store(store_error) => {
+                    write!(f, "Backing store failed: ")?;
+                    store_error.fmt(f)
+                }
+            }
+        }
+    }
+
+    /// A read-through, write-through LRUCache over a backing store.
+    pub struct ThroughCache<K, V, S>
+    where
+        K: Eq + Hash,
+    {
+        cache: LRUCache<K, V>,
+        store: S,
+    }
+
+    impl<K, V, S> ThroughCache<K, V, S>
+    where
+        K: Eq + Hash + Copy,
+        S: CacheStore<K, V>,
+    {
+        /// Creates a ThroughCache with the given capacity over the given store. A zero
+        /// capacity ThroughCache is unusable.
+        pub fn with_capacity(capacity: usize, store: S) -> Self {
+            ThroughCache {
+                cache: LRUCache::with_capacity(capacity),
+                store,
+            }
+        }
+
+        pub fn cache(&self) -> &LRUCache<K, V> {
+            &self.cache
+        }
+
+        pub fn store(&self) -> &S {
+            &self.store
+        }
+
+        pub fn store_mut(&mut self) -> &mut S {
+            &mut self.store
+        }
+
+        /// Returns a reference to the value associated with the given key, loading it from
+        /// the store on a cache miss. If the store has no value either, we return a
+        /// "cache-miss" error.
+        pub fn get(&mut self, key: &K) -> Result<&V, StoreError<S::Error>> {
+            if !self.cache.contains(key) {
+                let value = self
+                    .store
+                    .load(key)
+                    .map_err(StoreError::Store)?
+                    .ok_or(CacheError::CacheMiss)?;
+                self.cache.insert(*key, value)?;
+            }
+
+            Ok(self.cache.query(key)?)
+        }
+
+        /// Writes the given key value pair to the store, then inserts it into the cache. If
+        /// the store fails, the cache is left untouched. A zero capacity cache fails before
+        /// the store is written.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), StoreError<S::Error>> {
+            if self.cache.capacity() == 0 {
+                return Err(CacheError::CacheBroken(ListError::ListEmpty).into());
+            }
+
+            self.store.store(&key, &value).map_err(StoreError::Store)?;
+            Ok(self.cache.insert(key, value)?)
+        }
+
+        /// Deletes the given key from the store, then removes it from the cache, returning
+        /// the cached value if there was one. If the store fails, the cache is left
+        /// untouched.
+        pub fn remove(&mut self, key: &K) -> Result<Option<V>, StoreError<S::Error>> {
+            self.store.delete(key).map_err(StoreError::Store)?;
+            match self.cache.remove(key) {
+                Ok(value) => Ok(Some(value)),
+                Err(CacheError::CacheMiss) => Ok(None),
+                Err(cache_error) => Err(cache_error.into()),
+            }
+        }
+    }
+
+    /// A Least-Recently-Used write-back cache over a backing store. Writes only reach the
+    /// store when flushed, or when their entry is evicted. Entries are kept in an LRUCache
+    /// along with the time their value was first written without being stored since.
+    pub struct WriteBackCache<K, V, S>
+    where
+        K: Eq + Hash,
+    {
+        cache: LRUCache<K, (V, Option<Instant>)>,
+        store: S,
+    }
+
+    impl<K, V, S> WriteBackCache<K, V, S>
+    where
+        K: Eq + Hash + Copy,
+        S: CacheStore<K, V>,
+    {
+        /// Creates a WriteBackCache with the given capacity over the given store. A zero
+        /// capacity WriteBackCache is unusable.
+        pub fn with_capacity(capacity: usize, store: S) -> Self {
+            WriteBackCache {
+                cache: LRUCache::with_capacity(capacity),
+                store,
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.cache.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.cache.is_empty()
+        }
+
+        pub fn store(&self) -> &S {
+            &self.store
+        }
+
+        pub fn store_mut(&mut self) -> &mut S {
+            &mut self.store
+        }
+
+        /// Returns whether the entry for the given key holds a write not yet stored.
+        pub fn is_dirty(&self, key: &K) -> bool {
+            self.cache
+                .peek(key)
+                .is_ok_and(|(_, dirty_since)| dirty_since.is_some())
+        }
+
+        /// Returns the number of entries holding writes not yet stored.
+        pub fn dirty_len(&self) -> usize {
+            self.cache
+                .iter()
+                .filter(|(_, (_, dirty_since))| dirty_since.is_some())
+                .count()
+        }
+
+        /// Stores the value of the entry for the given key, if it is dirty.
+        fn write_back(&mut self, key: &K) -> Result<(), StoreError<S::Error>> {
+            let (value, dirty_since) = self.cache.peek_mut(key)?;
+            if dirty_since.is_some() {
+                self.store.store(key, value).map_err(StoreError::Store)?;
+                *dirty_since = None;
+            }
+
+            Ok(())
+        }
+
+        /// Stores the values of the entries dirty since a time matching the given
+        /// predicate, from the least to the most recently used one, stopping at the first
+        /// failure.
+        fn write_back_where<F>(&mut self, mut predicate: F) -> Result<(), StoreError<S::Error>>
+        where
+            F: FnMut(Instant) -> bool,
+        {
+            let keys: Vec<K> = self
+                .cache
+                .iter()
+                .filter(|(_, (_, dirty_since))| dirty_since.is_some_and(&mut predicate))
+                .map(|(key, _)| *key)
+                .collect();
+
+            for key in keys {
+                self.write_back(&key)?;
+            }
+
+            Ok(())
+        }
+
+        /// Writes back the least recently used entry if inserting the given key evicts it,
+        /// so that the entry is kept if the write-back fails.
+        fn make_room(&mut self, key: &K) -> Result<(), StoreError<S::Error>> {
+            if self.cache.len() < self.cache.capacity() || self.cache.contains(key) {
+                return Ok(());
+            }
+
+            if let Some((&lru_key, _)) = self.cache.peek_lru() {
+                self.write_back(&lru_key)?;
+            }
+
+            Ok(())
+        }
+
+        /// Returns a reference to the value associated with the given key, loading it from
+        /// the store on a cache miss. If the store has no value either, we return a
+        /// "cache-miss" error.
+        pub fn get(&mut self, key: &K) -> Result<&V, StoreError<S::Error>> {
+            if !self.cache.contains(key) {
+                let value = self
+                    .store
+                    .load(key)
+                    .map_err(StoreError::Store)?
+                    .ok_or(CacheError::CacheMiss)?;
+                self.make_room(key)?;
+                self.cache.insert(*key, (value, None))?;
+            }
+
+            let (value, _) = self.cache.query(key)?;
+            Ok(value)
+        }
+
+        /// Writes the given key value pair into this cache, marking the entry dirty. If this
+        /// cache is full, the least recently used entry is written back and removed.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), StoreError<S::Error>> {
+            let dirty_since = match self.cache.peek(&key) {
+                Ok((_, Some(dirty_since))) => *dirty_since,
+                _ => Instant::now(),
+            };
+
+            self.make_room(&key)?;
+            Ok(self.cache.insert(key, (value, Some(dirty_since)))?)
+        }
+
+        /// Deletes the given key from the store, then removes it from this cache, dropping
+        /// any write not yet stored. Returns the cached value if there was one.
+        pub fn remove(&mut self, key: &K) -> Result<Option<V>, StoreError<S::Error>> {
+            self.store.delete(key).map_err(StoreError::Store)?;
+            match self.cache.remove(key) {
+                Ok((value, _)) => Ok(Some(value)),
+                Err(CacheError::CacheMiss) => Ok(None),
+                Err(cache_error) => Err(cache_error.into()),
+            }
+        }
+
+        /// Stores every dirty entry, stopping at the first failure. Entries failing to be
+        /// stored stay dirty.
+        pub fn flush(&mut self) -> Result<(), StoreError<S::Error>> {
+            self.write_back_where(|_| true)
+        }
+
+        /// Stores the entry for the given key, if it is dirty. If no entry is found, we
+        /// return a "cache-miss" error.
+        pub fn flush_key(&mut self, key: &K) -> Result<(), StoreError<S::Error>> {
+            self.write_back(key)
+        }
+
+        /// Stores every entry which, at the given time, has been dirty for longer than the
+        /// given age, stopping at the first failure.
+        pub fn flush_older_than(
+            &mut self,
+            age: Duration,
+            now: Instant,
+        ) -> Result<(), StoreError<S::Error>> {
+            self.write_back_where(|dirty_since| now.duration_since(dirty_since) > age)
+        }
+    }
+
+    /// Writes queued by a WriteBehindCache, shared with its background thread.
+    struct WriteQueue<K, V, E> {
+        queued: HashMap<K, V>,
+        errors: HashMap<K, E>,
+        shutdown: bool,
+    }
+
+    struct WriteBehindShared<K, V, S, E> {
+        queue: Mutex<WriteQueue<K, V, E>>,
+        wake: Condvar,
+        store: Mutex<S>,
+    }
+
+    fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        mutex.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    impl<K, V, S> WriteBehindShared<K, V, S, S::Error>
+    where
+        K: Eq + Hash + Copy,
+        S: CacheStore<K, V>,
+    {
+        /// Writes all queued values to the store. Failed writes are recorded, and queued
+        /// again if asked to retry and no newer write has been queued since. Returns the
+        /// number of failed writes.
+        fn write_queued(&self, retry: bool) -> usize {
+            // the store stays locked until the batch is written, so that loads never see
+            // the store behind a write taken off the queue
+            let mut store = lock(&self.store);
+            let batch = std::mem::take(&mut lock(&self.queue).queued);
+
+            let mut failed = Vec::new();
+            for (key, value) in batch {
+                if let Err(error) = store.store(&key, &value) {
+                    failed.push((key, value, error));
+                }
+            }
+
+            let failures = failed.len();
+            let mut queue = lock(&self.queue);
+            for (key, value, error) in failed {
+                if retry && !queue.queued.contains_key(&key) {
+                    queue.queued.insert(key, value);
+                }
+                queue.errors.insert(key, error);
+            }
+
+            failures
+        }
+
+        /// Writes batches until shut down. A batch goes out once the given number of writes
+        /// are queued, or the given interval has passed since the last batch. After a batch
+        /// with failed writes, the next one waits for the interval to pass, so that failing
+        /// writes are not retried back to back.
+        fn run(&self, batch_size: usize, interval: Duration) {
+            let mut backoff = false;
+            loop {
+                let deadline = Instant::now() + interval;
+                let mut queue = lock(&self.queue);
+                while !queue.shutdown && (backoff || queue.queued.len() < batch_size) {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+
+                    queue = self
+                        .wake
+                        .wait_timeout(queue, deadline - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0;
+                }
+
+                let shutdown = queue.shutdown;
+                drop(queue);
+
+                // writes failing at shutdown stay queued, for `close` to hand back
+                backoff = self.write_queued(true) > 0;
+                if shutdown {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Number of queued writes sent to the store in one batch by default.
+    pub const DEFAULT_BATCH_SIZE: usize = 64;
+
+    /// Longest time writes wait in the queue by default.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Shortest interval between batches, so that the background thread never spins.
+    pub const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+    /// A Least-Recently-Used write-behind cache over a backing store. Writes are queued and
+    /// sent to the store in batches by a background thread, with repeated writes to a key
+    /// coalesced into one. Misses are served from the queue before the store, so that
+    /// evicting an entry never loses a write still queued.
+    pub struct WriteBehindCache<K, V, S>
+    where
+        K: Eq + Hash,
+        S: CacheStore<K, V>,
+    {
+        cache: LRUCache<K, V>,
+        shared: Arc<WriteBehindShared<K, V, S, S::Error>>,
+        batch_size: usize,
+        writer: Option<JoinHandle<()>>,
+    }
+
+    impl<K, V, S> WriteBehindCache<K, V, S>
+    where
+        K: Eq + Hash + Copy + Send + 'static,
+        V: Clone + Send + 'static,
+        S: CacheStore<K, V> + Send + 'static,
+        S::Error: Send + 'static,
+    {
+        /// Creates a WriteBehindCache with the given capacity over the given store, writing
+        /// batches of `DEFAULT_BATCH_SIZE` at least every `DEFAULT_INTERVAL`. A zero
+        /// capacity WriteBehindCache is unusable.
+        pub fn with_capacity(capacity: usize, store: S) -> Self {
+            Self::with_parameters(capacity, store, DEFAULT_BATCH_SIZE, DEFAULT_INTERVAL)
+        }
+
+        /// Creates a WriteBehindCache with the given capacity over the given store. Queued
+        /// writes are sent to the store once the given batch size is reached, or the given
+        /// interval has passed. A zero batch size is taken as one, and an interval shorter
+        /// than `MIN_INTERVAL` as `MIN_INTERVAL`.
+        pub fn with_parameters(
+            capacity: usize,
+            store: S,
+            batch_size: usize,
+            interval: Duration,
+        ) -> Self {
+            let batch_size = batch_size.max(1);
+            let interval = interval.max(MIN_INTERVAL);
+            let shared = Arc::new(WriteBehindShared {
+                queue: Mutex::new(WriteQueue {
+                    queued: HashMap::new(),
+                    errors: HashMap::new(),
+                    shutdown: false,
+                }),
+                wake: Condvar::new(),
+                store: Mutex::new(store),
+            });
+
+            let writer_shared = Arc::clone(&shared);
+            let writer = thread::spawn(move || writer_shared.run(batch_size, interval));
+
+            WriteBehindCache {
+                cache: LRUCache::with_capacity(capacity),
+                shared,
+                batch_size,
+                writer: Some(writer),
+            }
+        }
+
+        /// Returns the cache in front of the store. Entries evicted from it may still have
+        /// writes queued, which `get` serves from the queue.
+        pub fn cache(&self) -> &LRUCache<K, V> {
+            &self.cache
+        }
+
+        /// Returns the number of writes queued for the store.
+        pub fn pending_len(&self) -> usize {
+            lock(&self.shared.queue).queued.len()
+        }
+
+        /// Returns the errors of the writes which failed in the background since the last
+        /// call, with the latest error per key. Failed writes are retried with the next
+        /// batch.
+        pub fn take_errors(&mut self) -> Vec<(K, S::Error)> {
+            lock(&self.shared.queue).errors.drain().collect()
+        }
+
+        /// Returns a reference to the value associated with the given key. On a cache miss,
+        /// the value is taken from the write queue, or else loaded from the store. If the
+        /// store has no value either, we return a "cache-miss" error.
+        pub fn get(&mut self, key: &K) -> Result<&V, StoreError<S::Error>> {
+            if !self.cache.contains(key) {
+                let mut store = lock(&self.shared.store);
+                let queued = lock(&self.shared.queue).queued.get(key).cloned();
+                let value = match queued {
+                    Some(value) => value,
+                    None => store
+                        .load(key)
+                        .map_err(StoreError::Store)?
+                        .ok_or(CacheError::CacheMiss)?,
+                };
+                drop(store);
+
+                self.cache.insert(*key, value)?;
+            }
+
+            Ok(self.cache.query(key)?)
+        }
+
+        /// Inserts the given key value pair into the cache, and queues it to be written to
+        /// the store, replacing any write queued for the key.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), StoreError<S::Error>> {
+            self.cache.insert(key, value.clone())?;
+
+            let mut queue = lock(&self.shared.queue);
+            queue.queued.insert(key, value);
+            if queue.queued.len() >= self.batch_size {
+                self.shared.wake.notify_one();
+            }
+
+            Ok(())
+        }
+
+        /// Drops any write queued for the given key and deletes it from the store, then
+        /// removes it from the cache. Returns the cached value if there was one.
+        pub fn remove(&mut self, key: &K) -> Result<Option<V>, StoreError<S::Error>> {
+            {
+                let mut store = lock(&self.shared.store);
+                lock(&self.shared.queue).queued.remove(key);
+                store.delete(key).map_err(StoreError::Store)?;
+            }
+
+            match self.cache.remove(key) {
+                Ok(value) => Ok(Some(value)),
+                Err(CacheError::CacheMiss) => Ok(None),
+                Err(cache_error) => Err(cache_error.into()),
+            }
+        }
+
+        /// Writes all queued values to the store right away. If any write fails, it stays
+        /// queued, and its error is available from `take_errors`. Returns the number of
+        /// failed writes.
+        pub fn flush(&mut self) -> usize {
+            self.shared.write_queued(true)
+        }
+
+        /// Writes the values still queued, and stops the background thread. If any write
+        /// fails, we return the writes which never reached the store, with their errors.
+        pub fn close(mut self) -> Result<(), Vec<(K, V, S::Error)>> {
+            self.shutdown();
+
+            let mut queue = lock(&self.shared.queue);
+            let queued = std::mem::take(&mut queue.queued);
+            let unwritten: Vec<_> = queued
+                .into_iter()
+                .filter_map(|(key, value)| Some((key, value, queue.errors.remove(&key)?)))
+                .collect();
+
+            if unwritten.is_empty() {
+                Ok(())
+            } else {
+                Err(unwritten)
+            }
+        }
+    }
+
+    impl<K, V, S> WriteBehindCache<K, V, S>
+    where
+        K: Eq + Hash,
+        S: CacheStore<K, V>,
+    {
+        fn shutdown(&mut self) {
+            lock(&self.shared.queue).shutdown = true;
+            self.shared.wake.notify_one();
+
+            if let Some(writer) = self.writer.take() {
+                let _ = writer.join();
+            }
+        }
+    }
+
+    impl<K, V, S> Drop for WriteBehindCache<K, V, S>
+    where
+        K: Eq + Hash,
+        S: CacheStore<K, V>,
+    {
+        /// Writes the values still queued, and stops the background thread. Writes failing
+        /// at this point are lost; `close` hands them back instead.
+        fn drop(&mut self) {
+            self.shutdown();
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::sync::mpsc;
+
+        /// Store over a map, counting loads and stores, and failing while marked
+        /// unavailable.
+        #[derive(Default)]
+        struct TestStore {
+            values: HashMap<i32, i32>,
+            loads: usize,
+            stores: usize,
+            unavailable: bool,
+        }
+
+        impl CacheStore<i32, i32> for TestStore {
+            type Error = &'static str;
+
+            fn load(&mut self, key: &i32) -> Result<Option<i32>, Self::Error> {
+                if self.unavailable {
+                    return Err("unavailable");
+                }
+                self.loads += 1;
+                Ok(self.values.get(key).copied())
+            }
+
+            fn store(&mut self, key: &i32, value: &i32) -> Result<(), Self::Error> {
+                if self.unavailable {
+                    return Err("unavailable");
+                }
+                self.stores += 1;
+                self.values.insert(*key, *value);
+                Ok(())
+            }
+
+            fn delete(&mut self, key: &i32) -> Result<(), Self::Error> {
+                if self.unavailable {
+                    return Err("unavailable");
+                }
+                self.values.remove(key);
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn through_cache_consistency() {
+            let store = TestStore {
+                values: HashMap::from([(0, 0), (1, 1), (2, 2)]),
+                ..Default::default()
+            };
+            let mut through_cache = ThroughCache::with_capacity(2, store);
+
+            assert_eq!(through_cache.get(&0), Ok(&0));
+            assert_eq!(through_cache.get(&0), Ok(&0));
+            assert_eq!(through_cache.store().loads, 1);
+
+            // evicted entries are loaded again
+            assert_eq!(through_cache.get(&1), Ok(&1));
+            assert_eq!(through_cache.get(&2), Ok(&2));
+            assert_eq!(through_cache.get(&0), Ok(&0));
+            assert_eq!(through_cache.store().loads, 4);
+
+            assert!(through_cache.insert(3, 3).is_ok());
+            assert_eq!(through_cache.store().values.get(&3), Some(&3));
+            assert_eq!(through_cache.get(&3), Ok(&3));
+            assert_eq!(through_cache.store().loads, 4);
+
+            through_cache.store_mut().unavailable = true;
+            assert_eq!(through_cache.get(&3), Ok(&3));
+            assert_eq!(through_cache.get(&1), Err(StoreError::Store("unavailable")));
+            assert_eq!(
+                through_cache.insert(3, -3),
+                Err(StoreError::Store("unavailable"))
+            );
+            assert_eq!(
+                through_cache.remove(&3),
+                Err(StoreError::Store("unavailable"))
+            );
+            assert_eq!(through_cache.get(&3), Ok(&3));
+            assert_eq!(
+                StoreError::<&str>::Store("unavailable").to_string(),
+                "Backing store failed: unavailable"
+            );
+
+            through_cache.store_mut().unavailable = false;
+            assert_eq!(through_cache.remove(&3), Ok(Some(3)));
+            assert_eq!(through_cache.remove(&1), Ok(None));
+            assert_eq!(
+                through_cache.get(&1),
+                Err(StoreError::Cache(CacheError::CacheMiss))
+            );
+            assert_eq!(
+                through_cache.get(&3),
+                Err(StoreError::Cache(CacheError::CacheMiss))
+            );
+        }
+
+        #[test]
+        fn through_cache_zero_capacity() {
+            let store = TestStore {
+                values: HashMap::from([(0, 0)]),
+                ..Default::default()
+            };
+            let mut through_cache = ThroughCache::with_capacity(0, store);
+            let list_empty = StoreError::Cache(CacheError::CacheBroken(ListError::ListEmpty));
+
+            // writes fail before reaching the store
+            assert_eq!(through_cache.insert(1, 1), Err(list_empty.clone()));
+            assert_eq!(through_cache.store().stores, 0);
+            assert_eq!(through_cache.store().values.get(&1), None);
+
+            // values are loaded, but cannot be cached
+            assert_eq!(through_cache.get(&0), Err(list_empty));
+            assert_eq!(through_cache.store().loads, 1);
+            assert_eq!(through_cache.remove(&0), Ok(None));
+            assert!(through_cache.store().values.is_empty());
+            assert!(through_cache.cache().is_empty());
+        }
+
+        #[test]
+        fn through_cache_repeated_keys() {
+            let mut through_cache = ThroughCache::with_capacity(2, TestStore::default());
+            for ele in 0..3 {
+                assert!(through_cache.insert(0, ele).is_ok());
+            }
+            assert_eq!(through_cache.store().stores, 3);
+            assert_eq!(through_cache.store().values.get(&0), Some(&2));
+            assert_eq!(through_cache.cache().len(), 1);
+
+            // a repeated insert into a full cache evicts nothing, and marks the entry used
+            assert!(through_cache.insert(1, 1).is_ok());
+            assert!(through_cache.insert(0, 3).is_ok());
+            assert!(through_cache.insert(2, 2).is_ok());
+            assert!(!through_cache.cache().contains(&1));
+            assert_eq!(through_cache.get(&0), Ok(&3));
+            assert_eq!(through_cache.store().loads, 0);
+
+            // removing twice deletes twice, finding the entry cached once
+            assert_eq!(through_cache.remove(&0), Ok(Some(3)));
+            assert_eq!(through_cache.remove(&0), Ok(None));
+            assert_eq!(
+                through_cache.get(&0),
+                Err(StoreError::Cache(CacheError::CacheMiss))
+            );
+        }
+
+        #[test]
+        fn write_back_cache_consistency() {
+            let store = TestStore {
+                values: HashMap::from([(0, 0)]),
+                ..Default::default()
+            };
+            let mut write_back_cache = WriteBackCache::with_capacity(2, store);
+
+            assert_eq!(write_back_cache.get(&0), Ok(&0));
+            assert!(!write_back_cache.is_dirty(&0));
+            for ele in [1, 2, 1] {
+                assert!(write_back_cache.insert(1, ele).is_ok());
+            }
+            assert!(write_back_cache.is_dirty(&1));
+            assert_eq!(write_back_cache.store().stores, 0);
+
+            // clean entries are evicted without a write
+            assert!(write_back_cache.insert(2, 2).is_ok());
+            assert_eq!(write_back_cache.store().stores, 0);
+            assert_eq!(write_back_cache.dirty_len(), 2);
+
+            // dirty entries are written back when evicted
+            assert_eq!(write_back_cache.get(&0), Ok(&0));
+            assert_eq!(write_back_cache.store().values.get(&1), Some(&1));
+            assert_eq!(write_back_cache.store().stores, 1);
+
+            // a failed write-back keeps the entry
+            write_back_cache.store_mut().unavailable = true;
+            assert_eq!(
+                write_back_cache.insert(3, 3),
+                Err(StoreError::Store("unavailable"))
+            );
+            assert!(write_back_cache.is_dirty(&2));
+            assert_eq!(
+                write_back_cache.flush(),
+                Err(StoreError::Store("unavailable"))
+            );
+            assert_eq!(write_back_cache.len(), 2);
+
+            write_back_cache.store_mut().unavailable = false;
+            assert!(write_back_cache.insert(0, -1).is_ok());
+            let now = Instant::now();
+            assert!(write_back_cache
+                .flush_older_than(Duration::from_secs(3600), now)
+                .is_ok());
+            assert_eq!(write_back_cache.dirty_len(), 2);
+            assert!(write_back_cache.flush_key(&2).is_ok());
+            assert_eq!(write_back_cache.store().values.get(&2), Some(&2));
+            assert!(!write_back_cache.is_dirty(&2));
+            assert_eq!(
+                write_back_cache.flush_key(&3),
+                Err(StoreError::Cache(CacheError::CacheMiss))
+            );
+
+            let later = now + Duration::from_secs(3601);
+            assert!(write_back_cache
+                .flush_older_than(Duration::from_secs(3600), later)
+                .is_ok());
+            assert_eq!(write_back_cache.store().values.get(&0), Some(&-1));
+            assert_eq!(write_back_cache.dirty_len(), 0);
+            assert_eq!(write_back_cache.store().stores, 3);
+
+            assert!(write_back_cache.insert(4, 4).is_ok());
+            assert_eq!(write_back_cache.remove(&4), Ok(Some(4)));
+            assert!(write_back_cache.flush().is_ok());
+            assert_eq!(write_back_cache.store().values.get(&4), None);
+            assert_eq!(write_back_cache.store().stores, 3);
+        }
+
+        #[test]
+        fn write_back_cache_zero_capacity() {
+            let store = TestStore {
+                values: HashMap::from([(0, 0)]),
+                ..Default::default()
+            };
+            let mut write_back_cache = WriteBackCache::with_capacity(0, store);
+            let list_empty = StoreError::Cache(CacheError::CacheBroken(ListError::ListEmpty));
+
+            assert_eq!(write_back_cache.insert(1, 1), Err(list_empty.clone()));
+            assert_eq!(write_back_cache.get(&0), Err(list_empty));
+            assert_eq!(write_back_cache.store().stores, 0);
+            assert_eq!(write_back_cache.dirty_len(), 0);
+
+            assert!(write_back_cache.flush().is_ok());
+            assert_eq!(
+                write_back_cache.flush_key(&1),
+                Err(StoreError::Cache(CacheError::CacheMiss))
+            );
+            assert_eq!(write_back_cache.remove(&0), Ok(None));
+            assert!(write_back_cache.store().values.is_empty());
+            assert!(write_back_cache.is_empty());
+        }
+
+        #[test]
+        fn write_back_cache_repeated_keys() {
+            let mut write_back_cache = WriteBackCache::with_capacity(2, TestStore::default());
+            assert!(write_back_cache.insert(0, 0).is_ok());
+            let (_, first_write) = *write_back_cache.cache.peek(&0).unwrap();
+
+            // repeated writes keep the time the entry first became dirty
+            for ele in 1..3 {
+                assert!(write_back_cache.insert(0, ele).is_ok());
+            }
+            assert_eq!(write_back_cache.cache.peek(&0), Ok(&(2, first_write)));
+            assert_eq!(write_back_cache.dirty_len(), 1);
+
+            // a repeated insert into a full cache writes nothing back
+            assert!(write_back_cache.insert(1, 1).is_ok());
+            assert!(write_back_cache.insert(0, 3).is_ok());
+            assert_eq!(write_back_cache.store().stores, 0);
+            assert!(write_back_cache.insert(2, 2).is_ok());
+            assert_eq!(write_back_cache.store().values.get(&1), Some(&1));
+            assert_eq!(write_back_cache.store().stores, 1);
+
+            // once stored, the next write starts a new dirty period
+            assert!(write_back_cache.flush_key(&0).is_ok());
+            assert!(write_back_cache.flush_key(&0).is_ok());
+            assert_eq!(write_back_cache.store().stores, 2);
+            assert!(write_back_cache.insert(0, 4).is_ok());
+            let (_, dirty_since) = *write_back_cache.cache.peek(&0).unwrap();
+            assert!(dirty_since.is_some_and(|dirty_since| dirty_since >= first_write.unwrap()));
+        }
+
+        /// Store over a map shared with the test, since the cache hands the store to its
+        /// background thread. Every store attempt is reported on a channel, with whether it
+        /// succeeded, so that tests wait for the background thread without polling.
+        #[derive(Clone)]
+        struct SharedStore {
+            values: Arc<Mutex<HashMap<i32, i32>>>,
+            unavailable: Arc<Mutex<bool>>,
+            attempts: mpsc::Sender<(i32, bool)>,
+        }
+
+        impl CacheStore<i32, i32> for SharedStore {
+            type Error = &'static str;
+
+            fn load(&mut self, key: &i32) -> Result<Option<i32>, Self::Error> {
+                Ok(lock(&self.values).get(key).copied())
+            }
+
+            fn store(&mut self, key: &i32, value: &i32) -> Result<(), Self::Error> {
+                let available = !*lock(&self.unavailable);
+                if available {
+                    lock(&self.values).insert(*key, *value);
+                }
+                let _ = self.attempts.send((*key, available));
+
+                if available {
+                    Ok(())
+                } else {
+                    Err("unavailable")
+                }
+            }
+
+            fn delete(&mut self, key: &i32) -> Result<(), Self::Error> {
+                lock(&self.values).remove(key);
+                Ok(())
+            }
+        }
+
+        fn shared_store() -> (SharedStore, mpsc::Receiver<(i32, bool)>) {
+            let (attempts, receiver) = mpsc::channel();
+            let store = SharedStore {
+                values: Arc::default(),
+                unavailable: Arc::default(),
+                attempts,
+            };
+            (store, receiver)
+        }
+
+        /// Waits for the next store attempt of the background thread.
+        fn next_attempt(receiver: &mpsc::Receiver<(i32, bool)>) -> (i32, bool) {
+            receiver.recv_timeout(Duration::from_secs(10)).unwrap()
+        }
+
+        const HOUR: Duration = Duration::from_secs(3600);
+
+        #[test]
+        fn write_behind_cache_batches() {
+            let (store, receiver) = shared_store();
+            let mut write_behind_cache =
+                WriteBehindCache::with_parameters(2, store.clone(), 3, HOUR);
+
+            // repeated writes coalesce
+            assert!(write_behind_cache.insert(0, 0).is_ok());
+            assert!(write_behind_cache.insert(0, 1).is_ok());
+            assert!(write_behind_cache.insert(1, 1).is_ok());
+            assert_eq!(write_behind_cache.pending_len(), 2);
+
+            // a full batch goes out, while the evicted 0 stays readable
+            assert!(write_behind_cache.insert(2, 2).is_ok());
+            assert!(!write_behind_cache.cache().contains(&0));
+            assert_eq!(write_behind_cache.get(&0), Ok(&1));
+            let mut keys: Vec<_> = (0..3).map(|_| next_attempt(&receiver)).collect();
+            keys.sort();
+            assert_eq!(keys, [(0, true), (1, true), (2, true)]);
+            assert_eq!(lock(&store.values).get(&0), Some(&1));
+            assert_eq!(write_behind_cache.pending_len(), 0);
+            assert!(receiver.try_recv().is_err());
+        }
+
+        #[test]
+        fn write_behind_cache_flush() {
+            let (store, receiver) = shared_store();
+            let mut write_behind_cache =
+                WriteBehindCache::with_parameters(2, store.clone(), 100, HOUR);
+            assert_eq!(write_behind_cache.flush(), 0);
+
+            *lock(&store.unavailable) = true;
+            assert!(write_behind_cache.insert(3, 3).is_ok());
+            assert_eq!(write_behind_cache.flush(), 1);
+            assert_eq!(write_behind_cache.pending_len(), 1);
+            assert_eq!(write_behind_cache.take_errors(), [(3, "unavailable")]);
+            assert!(write_behind_cache.take_errors().is_empty());
+
+            *lock(&store.unavailable) = false;
+            assert_eq!(write_behind_cache.flush(), 0);
+            assert_eq!(lock(&store.values).get(&3), Some(&3));
+            assert_eq!(
+                receiver.try_iter().collect::<Vec<_>>(),
+                [(3, false), (3, true)]
+            );
+
+            // removing drops the queued write and deletes the key from the store
+            assert!(write_behind_cache.insert(4, 4).is_ok());
+            assert_eq!(write_behind_cache.remove(&4), Ok(Some(4)));
+            assert_eq!(write_behind_cache.remove(&3), Ok(Some(3)));
+            assert_eq!(write_behind_cache.remove(&3), Ok(None));
+            assert_eq!(write_behind_cache.pending_len(), 0);
+            assert_eq!(lock(&store.values).get(&3), None);
+            assert_eq!(
+                write_behind_cache.get(&3),
+                Err(StoreError::Cache(CacheError::CacheMiss))
+            );
+        }
+
+        #[test]
+        fn write_behind_cache_interval() {
+            let (store, receiver) = shared_store();
+            let mut write_behind_cache =
+                WriteBehindCache::with_parameters(2, store, 100, Duration::from_millis(10));
+            assert!(write_behind_cache.insert(5, 5).is_ok());
+            assert_eq!(next_attempt(&receiver), (5, true));
+
+            // a zero batch size and interval are clamped rather than spinning
+            let (store, receiver) = shared_store();
+            let mut write_behind_cache =
+                WriteBehindCache::with_parameters(2, store, 0, Duration::ZERO);
+            assert!(write_behind_cache.insert(8, 8).is_ok());
+            assert_eq!(next_attempt(&receiver), (8, true));
+        }
+
+        #[test]
+        fn write_behind_cache_backoff() {
+            let (store, receiver) = shared_store();
+            let mut write_behind_cache =
+                WriteBehindCache::with_parameters(2, store.clone(), 1, HOUR);
+            *lock(&store.unavailable) = true;
+            assert!(write_behind_cache.insert(6, 6).is_ok());
+            assert_eq!(next_attempt(&receiver), (6, false));
+
+            // failed writes are retried after the interval, not with the next full batch
+            *lock(&store.unavailable) = false;
+            assert!(write_behind_cache.insert(7, 7).is_ok());
+            assert!(receiver.recv_timeout(Duration::from_millis(50)).is_err());
+            assert_eq!(write_behind_cache.pending_len(), 2);
+            assert_eq!(write_behind_cache.flush(), 0);
+            assert_eq!(lock(&store.values).get(&6), Some(&6));
+        }
+
+        #[test]
+        fn write_behind_cache_close() {
+            // queued writes go out when the cache is dropped or closed
+            let (store, _receiver) = shared_store();
+            let mut write_behind_cache =
+                WriteBehindCache::with_parameters(2, store.clone(), 100, HOUR);
+            assert!(write_behind_cache.insert(0, 0).is_ok());
+            drop(write_behind_cache);
+            assert_eq!(lock(&store.values).get(&0), Some(&0));
+
+            let mut write_behind_cache =
+                WriteBehindCache::with_parameters(2, store.clone(), 100, HOUR);
+            assert!(write_behind_cache.insert(1, 1).is_ok());
+            assert_eq!(write_behind_cache.close(), Ok(()));
+            assert_eq!(lock(&store.values).get(&1), Some(&1));
+
+            // writes failing on close are handed back
+            let mut write_behind_cache =
+                WriteBehindCache::with_parameters(2, store.clone(), 100, HOUR);
+            assert!(write_behind_cache.insert(2, 2).is_ok());
+            assert!(write_behind_cache.insert(2, -2).is_ok());
+            *lock(&store.unavailable) = true;
+            assert_eq!(
+                write_behind_cache.close(),
+                Err(vec![(2, -2, "unavailable")])
+            );
+            assert_eq!(lock(&store.values).get(&2), None);
+        }
+
+        #[test]
+        fn write_behind_cache_zero_capacity() {
+            let (store, receiver) = shared_store();
+            lock(&store.values).insert(0, 0);
+            let mut write_behind_cache =
+                WriteBehindCache::with_parameters(0, store.clone(), 1, HOUR);
+            let list_empty = StoreError::Cache(CacheError::CacheBroken(ListError::ListEmpty));
+
+            // writes failing in the cache are never queued
+            assert_eq!(write_behind_cache.insert(1, 1), Err(list_empty.clone()));
+            assert_eq!(write_behind_cache.pending_len(), 0);
+            assert_eq!(write_behind_cache.get(&0), Err(list_empty));
+            assert_eq!(write_behind_cache.remove(&0), Ok(None));
+            assert_eq!(write_behind_cache.flush(), 0);
+            assert_eq!(write_behind_cache.close(), Ok(()));
+            assert!(lock(&store.values).is_empty());
+            assert!(receiver.try_recv().is_err());
+        }
+    }
+}
+
+pub mod negative {
+    //! Module providing a Least-Recently-Used Cache which also remembers keys known to be
+    //! absent, such as keys the backend found no value for. Negative entries expire after
+    //! their own time to live, and take up their own share of capacity.
+    //!
+    //! Usage:
+    //! ```
+    //! use generational_lru::negative::{NegativeCache, NegativeError};
+    //! use generational_lru::lrucache::CacheError;
+    //! use std::time::{Duration, Instant};
+    //!
+    //! let mut negative_cache =
+    //!     NegativeCache::<i32, i32>::with_capacities(10, 5, Duration::from_secs(60));
+    //! let now = Instant::now();
+    //!
+    //! assert_eq!(
+    //!     negative_cache.query(&0, now),
+    //!     Err(NegativeError::Cache(CacheError::CacheMiss))
+    //! );
+    //!
+    //! // the backend has no value for 0
+    //! assert!(negative_cache.insert_absent(0, now).is_ok());
+    //! assert_eq!(negative_cache.query(&0, now), Err(NegativeError::KnownAbsent));
+    //!
+    //! // until it gets one
+    //! assert!(negative_cache.insert(0, 0).is_ok());
+    //! assert_eq!(negative_cache.query(&0, now), Ok(&0));
+    //! assert_eq!(negative_cache.negative_len(), 0);
+    //! ```
+
+    use crate::list::{Link, LinkedList};
+    use crate::lrucache::{CacheError, LRUCache};
+    use std::{
+        collections::HashMap,
+        fmt::Display,
+        hash::Hash,
+        time::{Duration, Instant},
+    };
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum NegativeError {
+        Cache(CacheError),
+        KnownAbsent,
+        NoNegativeCapacity,
+    }
+
+    impl Display for NegativeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match &self {
+                NegativeError::Cache(cache_error) => cache_error.fmt(f),
+                NegativeError::KnownAbsent => write!(f, "Key is known to be absent."),
+                NegativeError::NoNegativeCapacity => {
+                    write!(f, "Cache has no room for negative entries.")
+                }
+            }
+        }
+    }
+
+    impl From<CacheError> for NegativeError {
+        fn from(cache_error: CacheError) -> Self {
+            NegativeError::Cache(cache_error)
+        }
+    }
+
+    /// Marker of a key known to be absent, until the given time.
+    struct Negative<K> {
+        key: K,
+        expires_at: Instant,
+    }
+
+    /// A Least-Recently-Used Cache with negative entries for keys known to be absent.
+    /// Present entries are kept in an LRUCache, and negative entries in a list of their
+    /// own on the side, in the order they expire.
+    pub struct NegativeCache<K, V>
+    where
+        K: Eq + Hash,
+    {
+        cache: LRUCache<K, V>,
+        negatives: LinkedList<Negative<K>>,
+        negative_refs: HashMap<K, Link>,
+        negative_ttl: Duration,
+    }
+
+    impl<K, V> NegativeCache<K, V>
+    where
+        K: Eq + Hash + Clone,
+    {
+        /// Creates a NegativeCache holding up to `capacity` present entries and
+        /// `negative_capacity` negative entries, the latter expiring after the given time
+        /// to live. A zero capacity NegativeCache is unusable.
+        pub fn with_capacities(
+            capacity: usize,
+            negative_capacity: usize,
+            negative_ttl: Duration,
+        ) -> Self {
+            NegativeCache {
+                cache: LRUCache::with_capacity(capacity),
+                negatives: LinkedList::with_capacity(negative_capacity),
+                negative_refs: HashMap::new(),
+                negative_ttl,
+            }
+        }
+
+        pub fn len(&self) -> usize {
+            self.cache.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.cache.is_empty()
+        }
+
+        /// Returns the number of negative entries, including expired ones not yet dropped.
+        pub fn negative_len(&self) -> usize {
+            self.negatives.len()
+        }
+
+        /// Drops the negative entries which have expired by the given time. Negative
+        /// entries all live for the same time, so they expire in the order they were
+        /// inserted.
+        fn drop_expired(&mut self, now: Instant) -> Result<(), CacheError> {
+            while self
+                .negatives
+                .peek_front()
+                .is_ok_and(|negative| negative.expires_at <= now)
+            {
+                let negative = self
+                    .negatives
+                    .pop_front()
+                    .map_err(CacheError::CacheBroken)?;
+                self.negative_refs.remove(&negative.key);
+            }
+
+            Ok(())
+        }
+
+        /// Removes the negative entry for the given key, if any.
+        fn clear_absent(&mut self, key: &K) -> Result<(), CacheError> {
+            if let Some(link) = self.negative_refs.remove(key) {
+                self.negatives
+                    .remove(&link)
                     .map_err(CacheError::CacheBroken)?;
-                block_ref.value = value;
-                return Ok(());
             }
 
-            if self.blocks.is_full() {
-                let block = self.blocks.pop_front().map_err(CacheError::CacheBroken)?;
-                self.block_refs.remove(&block.key);
+            Ok(())
+        }
+
+        /// Returns a reference to the value associated with the given key. If the key is
+        /// known to be absent at the given time, we return a "known-absent" error. If the
+        /// key is not present in the cache otherwise, we return a "cache-miss" error.
+        pub fn query(&mut self, key: &K, now: Instant) -> Result<&V, NegativeError> {
+            self.drop_expired(now)?;
+            if self.negative_refs.contains_key(key) {
+                return Err(NegativeError::KnownAbsent);
+            }
+
+            Ok(self.cache.query(key)?)
+        }
+
+        /// Removes the associated key value pair for the given key from this cache. If no
+        /// entry is found, we return a "cache-miss" error. A negative entry for the key is
+        /// removed all the same.
+        pub fn remove(&mut self, key: &K) -> Result<V, NegativeError> {
+            self.clear_absent(key)?;
+            Ok(self.cache.remove(key)?)
+        }
+
+        /// Inserts a new key value pair into this cache, removing any negative entry for
+        /// the key. If this cache is full, the least recently used entry is removed.
+        pub fn insert(&mut self, key: K, value: V) -> Result<(), NegativeError> {
+            self.clear_absent(&key)?;
+            Ok(self.cache.insert(key, value)?)
+        }
+
+        /// Records the given key as known to be absent from the given time, so that queries
+        /// for it return a "known-absent" error until its time to live passes. An entry for
+        /// the key is removed, and the time to live of a negative entry for the key
+        /// restarts. If the negative entries are at capacity, the one expiring first is
+        /// removed. Without room for negative entries, we return a "no-negative-capacity"
+        /// error and leave this cache untouched.
+        pub fn insert_absent(&mut self, key: K, now: Instant) -> Result<(), NegativeError> {
+            if self.negatives.capacity() == 0 {
+                return Err(NegativeError::NoNegativeCapacity);
+            }
+
+            self.drop_expired(now)?;
+            self.clear_absent(&key)?;
+            match self.cache.remove(&key) {
+                Ok(_) | Err(CacheError::CacheMiss) => {}
+                Err(cache_error) => return Err(cache_error.into()),
+            }
+
+            if self.negatives.is_full() {
+                let negative = self
+                    .negatives
+                    .pop_front()
+                    .map_err(CacheError::CacheBroken)?;
+                self.negative_refs.remove(&negative.key);
             }
 
             let link = self
-                .blocks
-                .push_back(Block { key, value })
+                .negatives
+                .push_back(Negative {
+                    key: key.clone(),
+                    expires_at: now + self.negative_ttl,
+                })
                 .map_err(CacheError::CacheBroken)?;
-            self.block_refs.insert(key, link);
+            self.negative_refs.insert(key, link);
 
             Ok(())
         }
@@ -1060,53 +9685,123 @@ pub mod lrucache {
     #[cfg(test)]
     mod tests {
         use super::*;
+        use crate::list::ListError;
+
+        const MINUTE: Duration = Duration::from_secs(60);
 
         #[test]
-        fn lru_cache_consistency() {
-            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(0);
+        fn negative_cache_consistency() {
+            let now = Instant::now();
+            let mut negative_cache = NegativeCache::<i32, i32>::with_capacities(0, 1, MINUTE);
             assert_eq!(
-                lru_cache.insert(0, 0),
-                Err(CacheError::CacheBroken(ListError::ListEmpty))
+                negative_cache.insert(0, 0),
+                Err(NegativeError::Cache(CacheError::CacheBroken(
+                    ListError::ListEmpty
+                )))
+            );
+            assert!(negative_cache.insert_absent(0, now).is_ok());
+            assert_eq!(
+                negative_cache.query(&0, now),
+                Err(NegativeError::KnownAbsent)
             );
 
-            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(2);
-            assert!(lru_cache.insert(1, 1).is_ok());
-            assert!(lru_cache.insert(2, 2).is_ok());
-            assert_eq!(lru_cache.query(&1), Ok(&1));
-            assert!(lru_cache.insert(3, 3).is_ok());
-            assert_eq!(lru_cache.query(&2), Err(CacheError::CacheMiss));
-            assert!(lru_cache.insert(1, -1).is_ok());
-            assert_eq!(lru_cache.query(&1), Ok(&-1));
-            assert!(lru_cache.insert(4, 4).is_ok());
-            assert_eq!(lru_cache.query(&3), Err(CacheError::CacheMiss));
-
-            let capacity = 5;
+            let mut negative_cache = NegativeCache::<i32, i32>::with_capacities(2, 2, MINUTE);
+            assert!(negative_cache.insert(0, 0).is_ok());
+            assert!(negative_cache.insert(1, 1).is_ok());
 
-            let mut lru_cache = LRUCache::<i32, i32>::with_capacity(capacity);
-            assert_eq!(lru_cache.query(&0), Err(CacheError::CacheMiss));
+            // negative entries neither take room from present ones nor evict them
+            assert!(negative_cache.insert_absent(2, now).is_ok());
+            assert!(negative_cache.insert_absent(3, now).is_ok());
+            assert_eq!(negative_cache.len(), 2);
+            assert_eq!(
+                negative_cache.query(&2, now),
+                Err(NegativeError::KnownAbsent)
+            );
+            assert_eq!(negative_cache.query(&1, now), Ok(&1));
 
-            for ele in 0..capacity {
-                let x = ele as i32;
-                assert!(lru_cache.insert(x, x).is_ok());
-            }
+            // the negative entry inserted first goes first
+            assert!(negative_cache.insert_absent(4, now).is_ok());
+            assert_eq!(
+                negative_cache.query(&2, now),
+                Err(NegativeError::Cache(CacheError::CacheMiss))
+            );
+            assert_eq!(negative_cache.negative_len(), 2);
 
-            for ele in 0..capacity {
-                let x = ele as i32;
-                assert_eq!(lru_cache.query(&x), Ok(&x));
-            }
+            // a present entry turns negative, and back
+            assert!(negative_cache.insert_absent(0, now).is_ok());
+            assert_eq!(
+                negative_cache.query(&0, now),
+                Err(NegativeError::KnownAbsent)
+            );
+            assert_eq!(negative_cache.len(), 1);
+            assert!(negative_cache.insert(0, -1).is_ok());
+            assert_eq!(negative_cache.query(&0, now), Ok(&-1));
+            assert_eq!(negative_cache.negative_len(), 1);
 
-            let x = capacity as i32;
-            assert!(lru_cache.insert(x, x).is_ok());
+            // removals clear negative entries
+            assert_eq!(
+                negative_cache.remove(&4),
+                Err(NegativeError::Cache(CacheError::CacheMiss))
+            );
+            assert_eq!(
+                negative_cache.query(&4, now),
+                Err(NegativeError::Cache(CacheError::CacheMiss))
+            );
+            assert_eq!(negative_cache.negative_len(), 0);
+        }
 
-            assert_eq!(lru_cache.query(&x), Ok(&x));
+        #[test]
+        fn negative_cache_expiry() {
+            let now = Instant::now();
+            let mut negative_cache = NegativeCache::<i32, i32>::with_capacities(2, 2, MINUTE);
+            assert!(negative_cache.insert_absent(0, now).is_ok());
+            assert!(negative_cache.insert_absent(1, now + MINUTE / 2).is_ok());
+
+            // a negative entry holds until its time to live passes
+            let later = now + MINUTE - Duration::from_secs(1);
+            assert_eq!(
+                negative_cache.query(&0, later),
+                Err(NegativeError::KnownAbsent)
+            );
+            assert_eq!(
+                negative_cache.query(&0, now + MINUTE),
+                Err(NegativeError::Cache(CacheError::CacheMiss))
+            );
+            assert_eq!(negative_cache.negative_len(), 1);
+            assert_eq!(
+                negative_cache.query(&1, now + MINUTE),
+                Err(NegativeError::KnownAbsent)
+            );
 
-            assert_eq!(lru_cache.query(&0), Err(CacheError::CacheMiss));
+            // marking a key absent again restarts its time to live
+            assert!(negative_cache.insert_absent(1, now + MINUTE).is_ok());
+            assert_eq!(
+                negative_cache.query(&1, now + MINUTE * 2 - Duration::from_secs(1)),
+                Err(NegativeError::KnownAbsent)
+            );
+            assert_eq!(
+                negative_cache.query(&1, now + MINUTE * 2),
+                Err(NegativeError::Cache(CacheError::CacheMiss))
+            );
+            assert_eq!(negative_cache.negative_len(), 0);
+        }
 
-            let x = capacity as i32 / 2;
-            assert_eq!(lru_cache.remove(&x), Ok(x));
+        #[test]
+        fn negative_cache_no_negative_capacity() {
+            let now = Instant::now();
+            let mut negative_cache = NegativeCache::<i32, i32>::with_capacities(2, 0, MINUTE);
+            assert!(negative_cache.insert(0, 0).is_ok());
 
-            assert_eq!(lru_cache.query(&x), Err(CacheError::CacheMiss));
-            assert_eq!(lru_cache.remove(&x), Err(CacheError::CacheMiss));
+            // without room for negative entries, the present entry survives
+            assert_eq!(
+                negative_cache.insert_absent(0, now),
+                Err(NegativeError::NoNegativeCapacity)
+            );
+            assert_eq!(negative_cache.query(&0, now), Ok(&0));
+            assert_eq!(
+                NegativeError::NoNegativeCapacity.to_string(),
+                "Cache has no room for negative entries."
+            );
         }
     }
 }